@@ -1,18 +1,19 @@
 use crate::{
   bounding_box::BoundingBox,
   point::Point,
+  scalar::Scalar,
 };
 
-pub trait Bounded {
+pub trait Bounded<const N: usize, S = f32> {
   // return the bounding box for this object
-  fn bounds(&self) -> BoundingBox;
+  fn bounds(&self) -> BoundingBox<N, S>;
 }
 
-impl Bounded for BoundingBox {
+impl<S: Scalar, const N: usize> Bounded<N, S> for BoundingBox<N, S> {
   fn bounds(&self) -> Self { self.clone() }
 }
 
-impl Bounded for Point {
-  fn bounds(&self) -> BoundingBox { BoundingBox::just(self) }
+impl<S: Scalar, const N: usize> Bounded<N, S> for Point<N, S> {
+  fn bounds(&self) -> BoundingBox<N, S> { BoundingBox::just(self) }
 }
 
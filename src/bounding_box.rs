@@ -1,39 +1,38 @@
-use crate::point::{Point};
+use crate::{
+  point::{Point},
+  scalar::Scalar,
+};
 
 #[derive(PartialEq, Debug, Clone, Copy)]
-pub struct BoundingBox {
-  ll: Point, rr: Point,
+pub struct BoundingBox<const N: usize, S = f32> {
+  ll: Point<N, S>, rr: Point<N, S>,
 }
 
-impl BoundingBox {
-  pub fn new(ll: Point, rr:Point) -> Self {
-    assert_eq!(ll.len(), rr.len());
+impl<S: Scalar, const N: usize> BoundingBox<N, S> {
+  pub fn new(ll: Point<N, S>, rr:Point<N, S>) -> Self {
     assert!(ll.iter().enumerate().all(|(i,d)| d <= rr[i]));
     BoundingBox{ll, rr}
   }
-  pub fn just(p: &Point) -> Self {
+  pub fn just(p: &Point<N, S>) -> Self {
     BoundingBox{
       ll: p.clone(),
       rr: p.clone(),
     }
   }
-  pub fn inf(_dim: usize) -> Self {
+  pub fn inf() -> Self {
     BoundingBox{
-      ll: Point::from(std::f32::NEG_INFINITY),
-      rr: Point::from(std::f32::INFINITY),
+      ll: Point::from(S::NEG_INFINITY),
+      rr: Point::from(S::INFINITY),
     }
   }
-  pub fn dim(&self) -> usize { self.ll.len() }
-  pub fn strictly_contains(&self, p: &Point) -> bool {
-    assert_eq!(self.ll.len(), p.len());
-    (0..p.len()).all(|d| self.ll[d] < p[d] && p[d] < self.rr[d])
+  pub fn dim(&self) -> usize { N }
+  pub fn strictly_contains(&self, p: &Point<N, S>) -> bool {
+    (0..N).all(|d| self.ll[d] < p[d] && p[d] < self.rr[d])
   }
-  pub fn contains(&self, p: &Point) -> bool {
-    assert_eq!(self.ll.len(), p.len());
+  pub fn contains(&self, p: &Point<N, S>) -> bool {
     (0..self.dim()).all(|d| self.ll[d] <= p[d] && p[d] <= self.rr[d])
   }
   pub fn union(&self, o: &Self) -> Self {
-    assert_eq!(self.dim(), o.dim());
     BoundingBox{
       ll: (0..self.dim()).map(|d| self.ll[d].min(o.ll[d])).collect(),
       rr: (0..self.dim()).map(|d| self.rr[d].max(o.rr[d])).collect(),
@@ -45,58 +44,58 @@ impl BoundingBox {
       rr: (0..self.dim()).map(|d| self.rr[d].min(o.rr[d])).collect(),
     }
   }
-  pub fn dist(&self, p: &Point) -> f32 {
-    assert_eq!(self.dim(), p.len());
-    (0..p.len()).map(|d| (self.ll[d] - p[d]).max(0.).max(p[d] - self.rr[d]))
-      .map(|v| v.powi(2))
-      .sum::<f32>()
+  pub fn dist(&self, p: &Point<N, S>) -> S {
+    (0..N).map(|d| (self.ll[d] - p[d]).max(S::ZERO).max(p[d] - self.rr[d]))
+      .map(|v| v * v)
+      .sum::<S>()
       .sqrt()
   }
-  pub fn expand_to(&mut self, p: &Point) -> bool {
+  pub fn expand_to(&mut self, p: &Point<N, S>) -> bool {
     if self.contains(p) { return false };
-    (0..p.len()).for_each(|d| {
+    (0..N).for_each(|d| {
       self.ll[d] = self.ll[d].min(p[d]);
       self.rr[d] = self.rr[d].max(p[d]);
     });
     true
   }
-  pub fn volume(&self) -> f32 {
-    (0..self.ll.len()).map(|d| self.rr[d] - self.ll[d]).product::<f32>()
+  // computed in f64 so that integer boxes as large as inf() do not overflow
+  pub fn volume(&self) -> f64 {
+    (0..N).map(|d| self.rr[d].to_f64() - self.ll[d].to_f64()).product()
   }
-  pub fn center(&self) -> Point {
-    use std::f32;
+  // sum of the length of each side
+  pub fn margin(&self) -> f64 {
+    (0..N).map(|d| self.rr[d].to_f64() - self.ll[d].to_f64()).sum()
+  }
+  pub fn center(&self) -> Point<N, S> {
     (0..self.dim()).map(|d| {
       let (a, b) = (self.ll[d], self.rr[d]);
-      if a.is_finite() && b.is_finite() { (a+b)/2.0 }
-      else if a.is_nan() || b.is_nan() { f32::NAN }
-      else if a.is_sign_positive() && b.is_sign_positive() { f32::INFINITY }
-      else if a.is_sign_negative() && b.is_sign_negative() { f32::NEG_INFINITY }
-      else { 0. }
+      if a.is_finite() && b.is_finite() { (a+b)/(S::ONE + S::ONE) }
+      else if a.is_nan() { a }
+      else if b.is_nan() { b }
+      else if a >= S::ZERO && b >= S::ZERO { S::INFINITY }
+      else if a <= S::ZERO && b <= S::ZERO { S::NEG_INFINITY }
+      else { S::ZERO }
     }).collect()
   }
-  pub fn quadrant(&self, corner: Vec<bool>) -> Self {
-    let (ll, rr) = corner.iter()
-      .enumerate()
-      .map(|(i,&c)| if c { self.rr[i] } else { self.ll[i] })
-      .zip(self.center().iter())
-      .map(|(a, b)| if a < b { (a, b) } else { (b, a) })
-      .unzip();
+  pub fn quadrant(&self, corner: [bool; N]) -> Self {
+    let center = self.center();
+    let (mut ll, mut rr) = (center, center);
+    corner.iter().enumerate().for_each(|(d, &c)| if c { rr[d] = self.rr[d] }
+      else { ll[d] = self.ll[d] });
     BoundingBox{
       ll: ll, rr: rr,
     }
   }
   pub fn surrounds(&self,  o: &Self) -> bool {
-    assert_eq!(self.dim(), o.dim());
     (0..self.dim()).all(|d| self.ll[d] <= o.ll[d] && self.rr[d] >= o.rr[d])
   }
   pub fn strictly_surrounds(&self, o: &Self) -> bool {
-    assert_eq!(self.dim(), o.dim());
     (0..self.dim()).all(|d| self.ll[d] < o.ll[d] && self.rr[d] > o.rr[d])
   }
-  pub fn on_edge(&self, p: &Point) -> bool {
+  pub fn on_edge(&self, p: &Point<N, S>) -> bool {
     (0..self.dim()).any(|d| self.ll[d] == p[d] || self.rr[d] == p[d])
   }
-  pub fn split_on(&self, d: usize, v: f32) -> (Self, Self) {
+  pub fn split_on(&self, d: usize, v: S) -> (Self, Self) {
     assert!(d < self.dim());
     assert!(self.ll[d] <= v && v <= self.rr[d]);
     let mut mid_ll = self.ll.clone();
@@ -110,11 +109,14 @@ impl BoundingBox {
     })
   }
   pub fn overlaps(&self, o: &Self) -> bool {
-    assert_eq!(self.dim(), o.dim());
     (0..self.dim()).all(|d| self.rr[d] > o.ll[d] && self.ll[d] < o.rr[d])
   }
-  pub fn min_on(&self, d: usize) -> f32 { self.ll[d] }
-  pub fn max_on(&self, d: usize) -> f32 { self.rr[d] }
+  // same as overlaps but also true if the boxes only share an edge
+  pub fn intersects(&self, o: &Self) -> bool {
+    (0..self.dim()).all(|d| self.rr[d] >= o.ll[d] && self.ll[d] <= o.rr[d])
+  }
+  pub fn min_on(&self, d: usize) -> S { self.ll[d] }
+  pub fn max_on(&self, d: usize) -> S { self.rr[d] }
 }
 
 
@@ -122,12 +124,12 @@ impl BoundingBox {
 mod bounding_box_test {
   use super::BoundingBox;
   use crate::point::Point;
-  fn small_box() -> BoundingBox {
+  fn small_box() -> BoundingBox<2> {
     BoundingBox::new(Point::from(&vec!(-5., -5.)), Point::from(&vec!(5.,5.)))
   }
   #[test]
   fn test_inf() {
-    let inf = BoundingBox::inf(2);
+    let inf = BoundingBox::<2>::inf();
     (0..10).for_each(|v| {
       let v = v as f32;
       assert!(inf.contains(&Point::from(&vec!(v, v))));
@@ -151,12 +153,12 @@ mod bounding_box_test {
     });
     assert!(!bb.strictly_contains(&Point::from(&vec!(5.,5.))));
     assert!(bb.on_edge(&Point::from(&vec!(5.,5.))));
-    assert_eq!(bb.volume(), 0.);
+    assert_eq!(bb.volume(), 100.);
   }
 
   #[test]
   fn test_dist() {
-    let origin: Point = Default::default();
+    let origin: Point<3> = Default::default();
     let v = Point::from((5.,0.,0.));
     let mut bb_origin = BoundingBox::just(&origin);
     assert_eq!(bb_origin.dist(&v), 5.0);
@@ -171,9 +173,32 @@ mod bounding_box_test {
   }
   #[test]
   fn test_expand() {
-    let mut empty = BoundingBox::just(&Default::default());
+    let mut empty = BoundingBox::<3>::just(&Default::default());
     assert!(empty.expand_to(&Point::from(5.)));
     assert!(!empty.contains(&Point::from(-5.)));
   }
+  #[test]
+  fn test_quadrant() {
+    let bb = small_box();
+    let q = bb.quadrant([true, false]);
+    assert_eq!(q, BoundingBox::new(Point::from((0., -5.)), Point::from((5., 0.))));
+    assert!(bb.surrounds(&q));
+    assert_eq!(q.volume(), 25.);
+  }
+  #[test]
+  fn test_int() {
+    let bb = BoundingBox::new(Point::from((-4i32, 0)), Point::from((4, 10)));
+    assert_eq!(bb.volume(), 80.);
+    assert_eq!(bb.margin(), 18.);
+    assert_eq!(bb.center(), Point::from((0, 5)));
+    assert_eq!(bb.dist(&Point::from((7, 14))), 5);
+    let inf = BoundingBox::<2, i32>::inf();
+    assert!(inf.surrounds(&bb));
+    assert_eq!(inf.center(), Point::default());
+    assert_eq!(inf.intersection(&bb), bb);
+    let side = i32::MAX as f64 - i32::MIN as f64;
+    assert_eq!(inf.volume(), side * side);
+    assert_eq!(inf.margin(), 2. * side);
+  }
 }
 
@@ -0,0 +1,142 @@
+use crate::{
+  point::Point,
+  scalar::Scalar,
+  kdtree::KDTree,
+};
+
+// Points stored alongside optional attributes, with one value of every present attribute for
+// each point at the same index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointCloud<const N: usize, S = f32> {
+  pub pts: Vec<Point<N, S>>,
+  pub normals: Option<Vec<Point<N, S>>>,
+  pub colors: Option<Vec<[u8; 3]>>,
+  pub intensities: Option<Vec<f32>>,
+  // other named attributes
+  pub channels: Vec<(String, Vec<f64>)>,
+}
+
+impl<const N: usize, S: Scalar> Default for PointCloud<N, S> {
+  fn default() -> Self { Self::from(vec!()) }
+}
+
+impl<const N: usize, S: Scalar> From<Vec<Point<N, S>>> for PointCloud<N, S> {
+  fn from(pts: Vec<Point<N, S>>) -> Self {
+    PointCloud{ pts, normals: None, colors: None, intensities: None, channels: vec!() }
+  }
+}
+
+// copies the values at each index
+fn pick<T: Clone>(v: &[T], indices: &[usize]) -> Vec<T> {
+  indices.iter().map(|&i| v[i].clone()).collect()
+}
+
+impl<const N: usize, S: Scalar> PointCloud<N, S> {
+  pub fn new() -> Self { Default::default() }
+  pub fn len(&self) -> usize { self.pts.len() }
+  pub fn is_empty(&self) -> bool { self.pts.is_empty() }
+  pub fn with_normals(mut self, normals: Vec<Point<N, S>>) -> Self {
+    assert_eq!(normals.len(), self.len());
+    self.normals = Some(normals);
+    self
+  }
+  pub fn with_colors(mut self, colors: Vec<[u8; 3]>) -> Self {
+    assert_eq!(colors.len(), self.len());
+    self.colors = Some(colors);
+    self
+  }
+  pub fn with_intensities(mut self, intensities: Vec<f32>) -> Self {
+    assert_eq!(intensities.len(), self.len());
+    self.intensities = Some(intensities);
+    self
+  }
+  pub fn with_channel(mut self, name: &str, values: Vec<f64>) -> Self {
+    self.set_channel(name, values);
+    self
+  }
+  // adds or replaces a named attribute
+  pub fn set_channel(&mut self, name: &str, values: Vec<f64>) {
+    assert_eq!(values.len(), self.len());
+    match self.channels.iter_mut().find(|(n, _)| n == name) {
+      Some((_, v)) => *v = values,
+      None => self.channels.push((name.to_string(), values)),
+    };
+  }
+  pub fn channel(&self, name: &str) -> Option<&[f64]> {
+    self.channels.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_slice())
+  }
+  // returns the points at indices along with their attributes, in the order of indices
+  pub fn select(&self, indices: &[usize]) -> Self {
+    PointCloud{
+      pts: pick(&self.pts, indices),
+      normals: self.normals.as_ref().map(|n| pick(n, indices)),
+      colors: self.colors.as_ref().map(|c| pick(c, indices)),
+      intensities: self.intensities.as_ref().map(|i| pick(i, indices)),
+      channels: self.channels.iter().map(|(n, v)| (n.clone(), pick(v, indices))).collect(),
+    }
+  }
+  // keeps the points and attributes where mask is true
+  pub fn select_mask(&self, mask: &[bool]) -> Self {
+    assert_eq!(mask.len(), self.len());
+    let indices: Vec<_> = (0..self.len()).filter(|&i| mask[i]).collect();
+    self.select(&indices)
+  }
+  // keeps the points for which f is true given each index and point
+  pub fn filter<F: Fn(usize, &Point<N, S>) -> bool>(&self, f: F) -> Self {
+    let indices: Vec<_> = self.pts.iter().enumerate()
+      .filter(|(i, p)| f(*i, p))
+      .map(|(i, _)| i)
+      .collect();
+    self.select(&indices)
+  }
+  // keeps every nth point, starting with the first
+  pub fn subsample(&self, n: usize) -> Self {
+    assert!(n > 0);
+    let indices: Vec<_> = (0..self.len()).step_by(n).collect();
+    self.select(&indices)
+  }
+  // builds a KDTree of the points whose values are their indices in this cloud
+  pub fn kdtree(&self) -> KDTree<usize, N, S> { KDTree::indexed(&self.pts) }
+}
+
+#[cfg(test)]
+mod cloud_test {
+  use super::PointCloud;
+  use crate::point::Point;
+  fn line() -> PointCloud<2> {
+    PointCloud::from((0..6).map(|i| Point::from((i as f32, 0.))).collect::<Vec<_>>())
+      .with_normals(vec!(Point::from((0., 1.)); 6))
+      .with_colors((0..6).map(|i| [i as u8; 3]).collect())
+      .with_intensities((0..6).map(|i| i as f32 * 10.).collect())
+      .with_channel("time", (0..6).map(|i| i as f64 / 2.).collect())
+  }
+  #[test]
+  fn aligned_attributes() {
+    let c = line();
+    let s = c.select(&[4, 1]);
+    assert_eq!(s.pts, vec!(Point::from((4., 0.)), Point::from((1., 0.))));
+    assert_eq!(s.colors, Some(vec!([4; 3], [1; 3])));
+    assert_eq!(s.intensities, Some(vec!(40., 10.)));
+    assert_eq!(s.channel("time"), Some(&[2., 0.5][..]));
+    let f = c.filter(|_, p| p[0] >= 3.);
+    assert_eq!(f.len(), 3);
+    assert_eq!(f.channel("time"), Some(&[1.5, 2., 2.5][..]));
+    assert_eq!(c.subsample(4).intensities, Some(vec!(0., 40.)));
+    let mask = [true, false, false, false, false, true];
+    assert_eq!(c.select_mask(&mask), c.select(&[0, 5]));
+    assert_eq!(c.with_channel("time", vec!(0.; 6)).channels.len(), 1);
+  }
+  #[test]
+  fn kdtree_indices() {
+    let c = line();
+    let tree = c.kdtree();
+    assert_eq!(tree.size(), c.len());
+    let (_, &i) = tree.nearest(&Point::from((2.2, 1.))).unwrap();
+    assert_eq!(c.colors.as_ref().unwrap()[i], [2; 3]);
+    let mut near: Vec<_> = tree.within_radius(&Point::from((3., 0.)), 1.).iter()
+      .map(|&(_, &i, _)| i)
+      .collect();
+    near.sort();
+    assert_eq!(near, vec!(2, 3, 4));
+  }
+}
@@ -0,0 +1,135 @@
+use crate::{
+  point::Point,
+  scalar::Scalar,
+  kdtree::KDTree,
+};
+
+// Which cluster a point belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Label {
+  Noise,
+  // clusters are numbered from 0 in the order of their lowest index
+  Cluster(usize),
+}
+
+impl Label {
+  pub fn cluster(&self) -> Option<usize> {
+    match *self {
+      Label::Noise => None,
+      Label::Cluster(c) => Some(c),
+    }
+  }
+  pub fn is_noise(&self) -> bool { *self == Label::Noise }
+}
+
+// Density based clustering, where points with at least min_pts points within eps of them,
+// counting themselves, are core points. Core points within eps of each other share a cluster,
+// along with the other points within eps of them, and the rest are noise. A point near the core
+// of two clusters is put in the first one to reach it.
+pub fn dbscan<const N: usize, S: Scalar>(
+  pts: &[Point<N, S>],
+  eps: S,
+  min_pts: usize,
+) -> Vec<Label> {
+  let tree = KDTree::indexed(pts);
+  let near = |i: usize| -> Vec<usize> {
+    tree.within_radius(&pts[i], eps).into_iter().map(|(_, &j, _)| j).collect()
+  };
+  let mut labels: Vec<Option<Label>> = vec!(None; pts.len());
+  let mut clusters = 0;
+  for i in 0..pts.len() {
+    if labels[i].is_some() { continue };
+    let mut queue = near(i);
+    if queue.len() < min_pts {
+      labels[i] = Some(Label::Noise);
+      continue
+    }
+    let c = Label::Cluster(clusters);
+    clusters += 1;
+    labels[i] = Some(c);
+    while let Some(j) = queue.pop() {
+      match labels[j] {
+        // noise which is near a core point is on the border of its cluster
+        Some(Label::Noise) => labels[j] = Some(c),
+        Some(_) => {},
+        None => {
+          labels[j] = Some(c);
+          let next = near(j);
+          if next.len() >= min_pts { queue.extend(next) };
+        },
+      };
+    }
+  }
+  labels.into_iter().map(|l| l.unwrap()).collect()
+}
+
+// Connected components of the graph linking points within tolerance of each other, found by
+// flood fill. Keeps those with between min_size and max_size points inclusive, each as its
+// indices in increasing order, with the largest first and ties ordered by their lowest index.
+pub fn euclidean_clusters<const N: usize, S: Scalar>(
+  pts: &[Point<N, S>],
+  tolerance: S,
+  min_size: usize,
+  max_size: usize,
+) -> Vec<Vec<usize>> {
+  let tree = KDTree::indexed(pts);
+  let mut visited = vec!(false; pts.len());
+  let mut out = vec!();
+  for i in 0..pts.len() {
+    if visited[i] { continue };
+    visited[i] = true;
+    let mut cluster = vec!();
+    let mut stack = vec!(i);
+    while let Some(j) = stack.pop() {
+      cluster.push(j);
+      for (_, &k, _) in tree.within_radius(&pts[j], tolerance) {
+        if visited[k] { continue };
+        visited[k] = true;
+        stack.push(k);
+      }
+    }
+    if cluster.len() < min_size || cluster.len() > max_size { continue };
+    cluster.sort_unstable();
+    out.push(cluster);
+  }
+  out.sort_by_key(|c| std::cmp::Reverse(c.len()));
+  out
+}
+
+#[cfg(test)]
+mod cluster_test {
+  use super::{Label, dbscan, euclidean_clusters};
+  use crate::point::Point;
+  #[test]
+  fn density() {
+    // two lines of points with a gap, a border point and one far away
+    let mut pts: Vec<Point<2>> = (0..10).map(|i| Point::from((i as f32 * 0.5, 0.))).collect();
+    pts.extend((0..8).map(|i| Point::from((10. + i as f32 * 0.5, 1.))));
+    pts.push(Point::from((-0.9, 0.)));
+    pts.push(Point::from((20., 20.)));
+    let labels = dbscan(&pts, 1., 3);
+    assert!(labels[..10].iter().all(|&l| l == Label::Cluster(0)));
+    assert!(labels[10..18].iter().all(|&l| l == Label::Cluster(1)));
+    // within eps of a core point, but not a core point itself
+    assert_eq!(labels[18], Label::Cluster(0));
+    assert!(labels[19].is_noise());
+    assert_eq!(labels[19].cluster(), None);
+    // too sparse for any core points
+    assert!(dbscan(&pts, 0.4, 2).iter().all(Label::is_noise));
+    assert_eq!(dbscan(&pts, 0.4, 1)[19], Label::Cluster(19));
+    assert_eq!(dbscan::<2, f32>(&[], 1., 1), vec!());
+  }
+  #[test]
+  fn euclidean() {
+    // an L shaped chain, a pair and a single point
+    let mut pts: Vec<Point<2>> = vec!((5., 5.), (0., 0.), (5.5, 5.), (20., 0.))
+      .into_iter().map(Point::from).collect();
+    pts.extend((1..6).map(|i| Point::from((0., i as f32 * 0.8))));
+    pts.extend((0..4).map(|i| Point::from((0.8 + i as f32 * 0.8, 4.))));
+    assert_eq!(euclidean_clusters(&pts, 1., 1, 100),
+      vec!(vec!(1, 4, 5, 6, 7, 8, 9, 10, 11, 12), vec!(0, 2), vec!(3)));
+    assert_eq!(euclidean_clusters(&pts, 1., 2, 9), vec!(vec!(0, 2)));
+    assert_eq!(euclidean_clusters(&pts, 0.1, 1, 1).len(), pts.len());
+    assert_eq!(euclidean_clusters(&pts, 100., 1, 100).len(), 1);
+  }
+}
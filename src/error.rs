@@ -0,0 +1,83 @@
+use std::{fmt, io};
+
+// Errors from reading or writing files
+#[derive(Debug)]
+pub enum Error {
+  Io(io::Error),
+  // problem on a line of a text file, numbered from 1
+  Parse { line: usize, kind: ErrorKind },
+  // problem in the contents of a file which is not tied to a line, such as in binary data
+  Invalid(ErrorKind),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+  UnknownKeyword(String),
+  InvalidNumber(String),
+  // expected between min and max values but found some other number
+  ValueCount { min: usize, max: usize, found: usize },
+  // index which does not refer to an existing element
+  IndexOutOfRange(i64),
+  Malformed(String),
+  // a property or field which is needed was not declared
+  MissingProperty(String),
+  Unsupported(String),
+  UnexpectedEof,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+// result of parsing a single line or value, before its position is known
+pub type ParseResult<T> = std::result::Result<T, ErrorKind>;
+
+impl Error {
+  pub fn parse(line: usize, kind: ErrorKind) -> Self { Error::Parse{ line, kind } }
+  pub fn line(&self) -> Option<usize> {
+    match self {
+      Error::Parse{ line, .. } => Some(*line),
+      Error::Io(_) | Error::Invalid(_) => None,
+    }
+  }
+}
+
+impl From<io::Error> for Error {
+  fn from(e: io::Error) -> Self { Error::Io(e) }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Error::Io(e) => write!(f, "{}", e),
+      Error::Parse{ line, kind } => write!(f, "line {}: {}", line, kind),
+      Error::Invalid(kind) => write!(f, "{}", kind),
+    }
+  }
+}
+
+impl fmt::Display for ErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ErrorKind::UnknownKeyword(k) => write!(f, "unknown keyword {:?}", k),
+      ErrorKind::InvalidNumber(v) => write!(f, "invalid number {:?}", v),
+      ErrorKind::ValueCount{ min, max, found } if min == max =>
+        write!(f, "expected {} values, found {}", min, found),
+      ErrorKind::ValueCount{ min, max, found } if *max == usize::MAX =>
+        write!(f, "expected at least {} values, found {}", min, found),
+      ErrorKind::ValueCount{ min, max, found } =>
+        write!(f, "expected {} to {} values, found {}", min, max, found),
+      ErrorKind::IndexOutOfRange(i) => write!(f, "index {} out of range", i),
+      ErrorKind::Malformed(m) => write!(f, "{}", m),
+      ErrorKind::MissingProperty(p) => write!(f, "missing property {:?}", p),
+      ErrorKind::Unsupported(u) => write!(f, "unsupported {}", u),
+      ErrorKind::UnexpectedEof => write!(f, "unexpected end of file"),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Error::Io(e) => Some(e),
+      Error::Parse{ .. } | Error::Invalid(_) => None,
+    }
+  }
+}
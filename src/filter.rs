@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use crate::{
+  point::Point,
+  scalar::Scalar,
+  bounding_box::BoundingBox,
+  cloud::PointCloud,
+  kdtree::KDTree,
+  util::{average, variance},
+};
+
+// What each occupied voxel is replaced by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoxelPoint {
+  Centroid,
+  // the point which is closest to the centre of the voxel
+  NearestToCenter,
+}
+
+// Buckets the indices of pts into cubic cells with sides of length size, starting from the
+// lowest corner of their bounding box. Cells are ordered by their first point.
+pub fn voxel_groups<const N: usize, S: Scalar>(pts: &[Point<N, S>], size: S) -> Vec<Vec<usize>> {
+  assert!(size > S::ZERO);
+  let bounds = match voxel_bounds(pts) {
+    None => return vec!(),
+    Some(b) => b,
+  };
+  let size = size.to_f64();
+  let mut cells: HashMap<[i64; N], usize> = HashMap::new();
+  let mut groups: Vec<Vec<usize>> = vec!();
+  for (i, p) in pts.iter().enumerate() {
+    let mut key = [0; N];
+    for d in 0..N {
+      key[d] = ((p[d].to_f64() - bounds.min_on(d).to_f64()) / size).floor() as i64;
+    }
+    let next = groups.len();
+    let g = *cells.entry(key).or_insert(next);
+    if g == next { groups.push(vec!()) };
+    groups[g].push(i);
+  }
+  groups
+}
+
+fn voxel_bounds<const N: usize, S: Scalar>(pts: &[Point<N, S>]) -> Option<BoundingBox<N, S>> {
+  let mut b = BoundingBox::just(pts.first()?);
+  pts.iter().for_each(|p| { b.expand_to(p); });
+  Some(b)
+}
+
+fn centroid<const N: usize, S: Scalar>(pts: &[Point<N, S>], group: &[usize]) -> Point<N, S> {
+  (0..N).map(|d| S::from_f64(average(group.iter().map(|&i| pts[i][d].to_f64())))).collect()
+}
+
+// returns the index of the point in group which is closest to the centre of its voxel
+fn nearest_to_center<const N: usize, S: Scalar>(
+  pts: &[Point<N, S>],
+  group: &[usize],
+  bounds: &BoundingBox<N, S>,
+  size: S,
+) -> usize {
+  let size = size.to_f64();
+  let first = &pts[group[0]];
+  let center: Vec<_> = (0..N).map(|d| {
+    let min = bounds.min_on(d).to_f64();
+    (((first[d].to_f64() - min) / size).floor() + 0.5) * size + min
+  }).collect();
+  let dist_sq = |i: usize| (0..N).map(|d| (pts[i][d].to_f64() - center[d]).powi(2)).sum::<f64>();
+  *group.iter()
+    .min_by(|&&a, &&b| dist_sq(a).partial_cmp(&dist_sq(b)).unwrap_or(std::cmp::Ordering::Equal))
+    .unwrap()
+}
+
+// replaces the points in each occupied voxel by a single point
+pub fn voxel_downsample<const N: usize, S: Scalar>(
+  pts: &[Point<N, S>],
+  size: S,
+  out: VoxelPoint,
+) -> Vec<Point<N, S>> {
+  let groups = voxel_groups(pts, size);
+  match out {
+    VoxelPoint::Centroid => groups.iter().map(|g| centroid(pts, g)).collect(),
+    VoxelPoint::NearestToCenter => {
+      let bounds = voxel_bounds(pts).unwrap_or_else(BoundingBox::inf);
+      groups.iter().map(|g| pts[nearest_to_center(pts, g, &bounds, size)]).collect()
+    },
+  }
+}
+
+impl<const N: usize, S: Scalar> PointCloud<N, S> {
+  // downsamples the points as voxel_downsample does. For centroids the attributes of each
+  // voxel are averaged, with normals rescaled to unit length, otherwise the attributes of the
+  // nearest point are kept.
+  pub fn voxel_downsample(&self, size: S, out: VoxelPoint) -> Self {
+    let groups = voxel_groups(&self.pts, size);
+    if out == VoxelPoint::NearestToCenter {
+      let bounds = voxel_bounds(&self.pts).unwrap_or_else(BoundingBox::inf);
+      let kept: Vec<_> = groups.iter()
+        .map(|g| nearest_to_center(&self.pts, g, &bounds, size))
+        .collect();
+      return self.select(&kept)
+    }
+    let mean = |vals: &dyn Fn(usize) -> f64, g: &[usize]| average(g.iter().map(|&i| vals(i)));
+    let normals = self.normals.as_ref().map(|normals| groups.iter().map(|g| {
+      let n = centroid(normals, g);
+      let len = n.dist(&Point::default());
+      if len > S::ZERO { n.iter().map(|v| v / len).collect() } else { n }
+    }).collect());
+    let colors = self.colors.as_ref().map(|colors| groups.iter().map(|g| {
+      let mut c = [0; 3];
+      for k in 0..3 { c[k] = mean(&|i| colors[i][k] as f64, g).round() as u8 }
+      c
+    }).collect());
+    let intensities = self.intensities.as_ref().map(|intensities| groups.iter()
+      .map(|g| mean(&|i| intensities[i] as f64, g) as f32)
+      .collect());
+    let channels = self.channels.iter()
+      .map(|(name, v)| (name.clone(), groups.iter().map(|g| mean(&|i| v[i], g)).collect()))
+      .collect();
+    PointCloud{
+      pts: groups.iter().map(|g| centroid(&self.pts, g)).collect(),
+      normals,
+      colors,
+      intensities,
+      channels,
+    }
+  }
+}
+
+// Removes points whose mean distance to their k nearest neighbours is more than alpha standard
+// deviations above the mean of that distance over all points. Returns the remaining points and
+// the indices of those removed, in increasing order.
+pub fn statistical_outliers<const N: usize, S: Scalar>(
+  pts: &[Point<N, S>],
+  k: usize,
+  alpha: f64,
+) -> (Vec<Point<N, S>>, Vec<usize>) {
+  let removed = statistical_outlier_indices(pts, k, alpha);
+  let mut is_removed = vec!(false; pts.len());
+  removed.iter().for_each(|&i| is_removed[i] = true);
+  let inliers = pts.iter().zip(is_removed).filter(|(_, r)| !r).map(|(p, _)| *p).collect();
+  (inliers, removed)
+}
+
+fn statistical_outlier_indices<const N: usize, S: Scalar>(
+  pts: &[Point<N, S>],
+  k: usize,
+  alpha: f64,
+) -> Vec<usize> {
+  if pts.len() < 2 || k == 0 { return vec!() };
+  let tree = KDTree::indexed(pts);
+  // the nearest result for each point is itself, so it is skipped
+  let mean_dists: Vec<f64> = pts.iter()
+    .map(|p| average(tree.k_nearest(p, k + 1).iter().skip(1).map(|(_, _, d)| d.to_f64())))
+    .collect();
+  let threshold = average(mean_dists.iter().copied())
+    + alpha * variance(mean_dists.iter().copied()).sqrt();
+  (0..pts.len()).filter(|&i| mean_dists[i] > threshold).collect()
+}
+
+impl<const N: usize, S: Scalar> PointCloud<N, S> {
+  // see statistical_outliers, which also keeps the attributes of remaining points
+  pub fn remove_statistical_outliers(&self, k: usize, alpha: f64) -> (Self, Vec<usize>) {
+    let removed = statistical_outlier_indices(&self.pts, k, alpha);
+    let mut keep = vec!(true; self.len());
+    removed.iter().for_each(|&i| keep[i] = false);
+    (self.select_mask(&keep), removed)
+  }
+}
+
+// Whether each point has at least min_neighbors other points within distance r of it, so that
+// the mask can also be applied to other buffers aligned with pts.
+pub fn radius_outlier_mask<const N: usize, S: Scalar>(
+  pts: &[Point<N, S>],
+  r: S,
+  min_neighbors: usize,
+) -> Vec<bool> {
+  let tree = KDTree::indexed(pts);
+  pts.iter().enumerate()
+    .map(|(i, p)| tree.within_radius(p, r).iter().filter(|&&(_, &j, _)| j != i).count())
+    .map(|n| n >= min_neighbors)
+    .collect()
+}
+
+// indices of the points kept by radius_outlier_mask, in increasing order
+pub fn radius_inliers<const N: usize, S: Scalar>(
+  pts: &[Point<N, S>],
+  r: S,
+  min_neighbors: usize,
+) -> Vec<usize> {
+  let mask = radius_outlier_mask(pts, r, min_neighbors);
+  (0..pts.len()).filter(|&i| mask[i]).collect()
+}
+
+impl<const N: usize, S: Scalar> PointCloud<N, S> {
+  // keeps the points and attributes of those with at least min_neighbors within r
+  pub fn remove_radius_outliers(&self, r: S, min_neighbors: usize) -> Self {
+    self.select_mask(&radius_outlier_mask(&self.pts, r, min_neighbors))
+  }
+}
+
+#[cfg(test)]
+mod filter_test {
+  use super::{
+    VoxelPoint, voxel_downsample, voxel_groups, statistical_outliers, radius_outlier_mask,
+    radius_inliers,
+  };
+  use crate::{
+    point::Point,
+    cloud::PointCloud,
+  };
+  fn pts() -> Vec<Point<2>> {
+    vec!((0., 0.), (0.5, 0.5), (0.9, 0.1), (2.5, 0.), (2.9, 0.9), (0.2, 1.5))
+      .into_iter()
+      .map(Point::from)
+      .collect()
+  }
+  #[test]
+  fn voxels() {
+    let pts = pts();
+    assert_eq!(voxel_groups(&pts, 1.), vec!(vec!(0, 1, 2), vec!(3, 4), vec!(5)));
+    assert_eq!(voxel_groups::<2, f32>(&[], 1.), Vec::<Vec<usize>>::new());
+    let centroids = voxel_downsample(&pts, 1., VoxelPoint::Centroid);
+    assert_eq!(centroids.len(), 3);
+    assert!(centroids[0].dist(&Point::from((1.4 / 3., 0.2))) < 1e-6);
+    assert_eq!(centroids[1], Point::from((2.7, 0.45)));
+    let nearest = voxel_downsample(&pts, 1., VoxelPoint::NearestToCenter);
+    assert_eq!(nearest, vec!(pts[1], pts[3], pts[5]));
+    assert_eq!(voxel_downsample(&pts, 10., VoxelPoint::Centroid).len(), 1);
+    let ints: Vec<_> = (0..100).map(|i| Point::from((i, i % 7))).collect();
+    assert_eq!(voxel_downsample(&ints, 10, VoxelPoint::NearestToCenter).len(), 10);
+  }
+  #[test]
+  fn voxel_attributes() {
+    let c = PointCloud::from(pts())
+      .with_normals(vec!(Point::from((1., 0.)), Point::from((0., 1.)), Point::from((0., 1.)),
+        Point::from((1., 0.)), Point::from((1., 0.)), Point::from((0., 1.))))
+      .with_colors(vec!([0, 0, 0], [10, 20, 31], [20, 40, 0], [1, 1, 1], [3, 3, 3], [9, 9, 9]))
+      .with_channel("t", vec!(0., 1., 2., 3., 4., 5.));
+    let down = c.voxel_downsample(1., VoxelPoint::Centroid);
+    assert_eq!(down.colors, Some(vec!([10, 20, 10], [2, 2, 2], [9, 9, 9])));
+    assert_eq!(down.channel("t"), Some(&[1., 3.5, 5.][..]));
+    let n = down.normals.as_ref().unwrap()[0];
+    assert!((n.dist(&Point::default()) - 1.).abs() < 1e-6 && n[1] > n[0]);
+    let nearest = c.voxel_downsample(1., VoxelPoint::NearestToCenter);
+    assert_eq!(nearest, c.select(&[1, 3, 5]));
+  }
+  #[test]
+  fn statistical() {
+    // a grid with two far away points
+    let mut pts: Vec<Point<2>> = (0..100)
+      .map(|i| Point::from(((i % 10) as f32, (i / 10) as f32)))
+      .collect();
+    pts.insert(17, Point::from((30., 30.)));
+    pts.push(Point::from((-8., 4.)));
+    let (inliers, removed) = statistical_outliers(&pts, 4, 1.);
+    assert_eq!(removed, vec!(17, 101));
+    assert_eq!(inliers.len(), 100);
+    assert!(inliers.iter().all(|p| p[0] >= 0. && p[0] < 10.));
+    // corners of the grid are further from their neighbours, but within 4 deviations
+    let grid: Vec<_> = pts.iter().filter(|p| p[0] >= 0. && p[0] < 10.).copied().collect();
+    assert_eq!(statistical_outliers(&grid, 4, 1.).1, vec!(0, 9, 90, 99));
+    assert_eq!(statistical_outliers(&grid, 4, 4.).1, Vec::<usize>::new());
+    let c = PointCloud::from(pts).with_channel("i", (0..102).map(|i| i as f64).collect());
+    let (kept, removed) = c.remove_statistical_outliers(4, 1.);
+    assert_eq!(removed, vec!(17, 101));
+    assert_eq!(&kept.channel("i").unwrap()[16..18], &[16., 18.]);
+  }
+  #[test]
+  fn radius() {
+    let pts = pts();
+    // 3 and 4 are only close to each other, and 5 is alone
+    assert_eq!(radius_outlier_mask(&pts, 1., 1), vec!(true, true, true, true, true, false));
+    assert_eq!(radius_inliers(&pts, 1., 2), vec!(0, 1, 2));
+    assert_eq!(radius_inliers(&pts, 1., 0), vec!(0, 1, 2, 3, 4, 5));
+    // coincident points count as neighbours of each other
+    let same = vec!(Point::from((1., 1.)); 3);
+    assert_eq!(radius_inliers(&same, 0., 2), vec!(0, 1, 2));
+    let c = PointCloud::from(pts).with_intensities(vec!(0., 1., 2., 3., 4., 5.));
+    assert_eq!(c.remove_radius_outliers(1., 2).intensities, Some(vec!(0., 1., 2.)));
+  }
+}
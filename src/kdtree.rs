@@ -1,65 +1,94 @@
 use std::{
   cmp::Ordering,
-  f32,
+  collections::BinaryHeap,
 };
 use crate::{
   point::Point,
   bounding_box::BoundingBox,
+  iters::DepthFirst,
+  scalar::Scalar,
 };
 
+// KDTree over points with coordinates of type S, each carrying an associated value of type T.
+// Distances for integer coordinates are rounded, but searches compare squared distances
+// computed in f64, which are exact for integers and do not overflow.
 #[derive(Debug)]
-pub struct KDTree {
-  root: Option<KDNode>,
+pub struct KDTree<T, const N: usize, S = f32> {
+  root: Option<KDNode<T, N, S>>,
   size: usize,
 }
 
-impl KDTree {
+impl<T, const N: usize, S: Scalar> KDTree<T, N, S> {
   pub fn new() -> Self { KDTree{ root: None, size: 0 } }
-  pub fn add(&mut self, v: Point) {
+  pub fn add(&mut self, v: Point<N, S>, val: T) {
     self.size += 1;
     match &mut self.root {
-      None => { self.root.replace(KDNode::new(v, 0)); },
-      Some(ref mut r) => r.add(v),
+      None => { self.root.replace(KDNode::new(v, val, 0)); },
+      Some(ref mut r) => r.add(v, val),
     }
   }
-  // This RFC(https://github.com/rust-lang/rust/issues/55300) better be stabilized because it's
-  // super convenient
-  pub fn from(v: &mut [Point]) -> Self {
+  pub fn from(v: &mut [(Point<N, S>, T)]) -> Self where T: Clone {
     if v.len() == 0 { return KDTree::new(); }
-    let d = crate::point::variances(v)
-      .iter().enumerate().min_by(|(_,a),(_,b)| a.partial_cmp(&b).unwrap()).unwrap().0;
-    let p = v.partition_at_index_by((v.len()-1)/2,
-      |a,b| a[d].partial_cmp(&b[d]).unwrap_or(Ordering::Less));
+    let d = split_dim(v);
+    let p = v.select_nth_unstable_by((v.len()-1)/2,
+      |(a, _), (b, _)| cmp_coord(a[d], b[d]));
     KDTree{
       root: Some(KDNode::from(p, d)),
       size: v.len(),
     }
   }
-	pub fn remove(&mut self, p: &Point) -> bool {
-    let did_remove = if self.root.as_ref().map_or(false, |r| &r.item == p) {
-      let mut root = self.root.as_mut().unwrap();
-      let cmp_dim = root.cmp_dim;
-      () == if let Some(r_max) = root.r.as_mut().map(|r| r.find_max(cmp_dim).clone()) {
-        assert!(root.remove(&r_max));
-        root.item = r_max;
-      } else if let Some(l_min) = root.l.as_mut().map(|l| l.find_min(cmp_dim).clone()) {
-        assert!(root.remove(&l_min));
-        root.item = l_min;
-      } else { assert!(self.root.take().unwrap().is_leaf()) }
-    } else { self.root.as_mut().map_or(false, |r| r.remove(p)) };
-    self.size -= usize::from(did_remove);
-    did_remove
+  // removes some item at p, returning its value
+  pub fn remove(&mut self, p: &Point<N, S>) -> Option<T> {
+    let removed = if self.root.as_ref().is_some_and(|r| &r.item == p) {
+      match self.root.as_mut().unwrap().pop_item() {
+        Some(val) => Some(val),
+        None => self.root.take().map(|r| r.val),
+      }
+    } else { self.root.as_mut().and_then(|r| r.remove(p)) };
+    self.size -= usize::from(removed.is_some());
+    removed
   }
   pub fn is_empty(&self) -> bool { self.root.is_none() }
-  pub fn contains(&self, v: &Point) -> bool { self.root.as_ref().map_or(false, |r| r.contains(v)) }
-  pub fn nearest(&self, v: &Point) -> Option<&Point> { self.root.as_ref().map(|r| r.nearest(v).0) }
-  pub fn range<'a>(&self, b: &BoundingBox) -> Vec<Point> {
+  pub fn contains(&self, v: &Point<N, S>) -> bool { self.root.as_ref().is_some_and(|r| r.contains(v)) }
+  pub fn nearest(&self, v: &Point<N, S>) -> Option<(&Point<N, S>, &T)> {
+    self.root.as_ref().map(|r| r.nearest(v).0).map(|n| (&n.item, &n.val))
+  }
+  // returns up to k closest points to v with their distances, sorted closest first
+  pub fn k_nearest(&self, v: &Point<N, S>, k: usize) -> Vec<(&Point<N, S>, &T, S)> {
+    if k == 0 { return vec!() };
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    if let Some(r) = &self.root { r.k_nearest(v, k, &mut heap) };
+    heap.into_sorted_vec().into_iter()
+      .map(|Candidate(dist_sq, n)| (&n.item, &n.val, S::from_f64(dist_sq.sqrt())))
+      .collect()
+  }
+  // returns all points within distance r of v, in no particular order
+  pub fn within_radius(&self, v: &Point<N, S>, r: S) -> Vec<(&Point<N, S>, &T, S)> {
+    let mut buf = vec!();
+    let r = r.to_f64();
+    if let Some(root) = &self.root { root.within_radius(v, r * r, &mut buf) };
+    buf.into_iter().map(|(p, val, dist_sq)| (p, val, S::from_f64(dist_sq.sqrt()))).collect()
+  }
+  // same as within_radius but sorted closest first
+  pub fn within_radius_sorted(&self, v: &Point<N, S>, r: S) -> Vec<(&Point<N, S>, &T, S)> {
+    let mut buf = self.within_radius(v, r);
+    buf.sort_unstable_by(|(_, _, a), (_, _, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    buf
+  }
+  pub fn range(&self, b: &BoundingBox<N, S>) -> Vec<(&Point<N, S>, &T)> {
     let mut buf = vec!();
     self.root.as_ref().map(|r| r.range(b, &mut buf));
     buf
   }
-  pub fn find_max(&self, d: usize) -> Option<&Point> { self.root.as_ref().map(|r| r.find_max(d)) }
-  pub fn find_min(&self, d: usize) -> Option<&Point> { self.root.as_ref().map(|r| r.find_min(d)) }
+  pub fn iter(&self) -> impl Iterator<Item=(&Point<N, S>, &T)> {
+    self.root.iter().flat_map(DepthFirst::new).map(|n| (&n.item, &n.val))
+  }
+  pub fn find_max(&self, d: usize) -> Option<(&Point<N, S>, &T)> {
+    self.root.as_ref().map(|r| r.find_max(d)).map(|n| (&n.item, &n.val))
+  }
+  pub fn find_min(&self, d: usize) -> Option<(&Point<N, S>, &T)> {
+    self.root.as_ref().map(|r| r.find_min(d)).map(|n| (&n.item, &n.val))
+  }
   pub fn size(&self) -> usize { self.size }
   pub fn depth(&self) -> usize { self.root.as_ref().map_or(0, |r| r.depth()) }
   #[cfg(test)]
@@ -70,46 +99,77 @@ impl KDTree {
   }
 }
 
+impl<const N: usize, S: Scalar> KDTree<usize, N, S> {
+  // builds a tree whose values are the index of each point in pts
+  pub fn indexed(pts: &[Point<N, S>]) -> Self {
+    let mut items: Vec<_> = pts.iter().copied().zip(0..).collect();
+    KDTree::from(&mut items)
+  }
+}
+
+// the items below, at and above a median
+type Partition<'a, T, const N: usize, S> =
+  (&'a mut [(Point<N, S>, T)], &'a mut (Point<N, S>, T), &'a mut [(Point<N, S>, T)]);
+
+type Children<'a, T, const N: usize, S> = std::iter::Chain<
+  std::option::Iter<'a, Box<KDNode<T, N, S>>>,
+  std::option::Iter<'a, Box<KDNode<T, N, S>>>,
+>;
+
+// orders NaN after every other coordinate so that partitioning never panics
+fn cmp_coord<S: Scalar>(a: S, b: S) -> Ordering {
+  a.partial_cmp(&b).unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
+
+// picks the dimension with the greatest spread to split a non-empty set of items on
+fn split_dim<T, const N: usize, S: Scalar>(v: &[(Point<N, S>, T)]) -> usize {
+  (0..N).map(|d| crate::util::variance(v.iter().map(|(p, _)| p[d].to_f64())))
+    .enumerate().max_by(|(_, a), (_, b)| a.total_cmp(b)).unwrap().0
+}
+
 #[derive(Debug)]
-pub struct KDNode {
-  item: Point,
+pub struct KDNode<T, const N: usize, S = f32> {
+  item: Point<N, S>,
+  val: T,
   cmp_dim: usize,
   // l is lesser and equal
-  l: Option<Box<KDNode>>,
+  l: Option<Box<KDNode<T, N, S>>>,
   // right is greater and equal
-  r: Option<Box<KDNode>>,
+  r: Option<Box<KDNode<T, N, S>>>,
 }
 
-impl KDNode {
-  fn new(v: Point, dim: usize) -> Self {
+impl<T, const N: usize, S: Scalar> KDNode<T, N, S> {
+  fn new(v: Point<N, S>, val: T, dim: usize) -> Self {
     KDNode{
-      item: v, cmp_dim: dim,
+      item: v, val, cmp_dim: dim,
       l: None, r: None,
     }
   }
-  fn from(partition: (&mut [Point], &mut Point, &mut [Point]), cmp_dim: usize) -> Self {
+  fn from(partition: Partition<'_, T, N, S>, cmp_dim: usize) -> Self where T: Clone {
     let (below, median, above) = partition;
     let r = if below.is_empty() { None } else {
       let med = (below.len()-1)/2;
-      let d = crate::point::variances(below)
-        .iter().enumerate().min_by(|(_,a),(_,b)| a.partial_cmp(&b).unwrap()).unwrap().0;
-      let partition = below.partition_at_index_by(med, |a, b| a[d].partial_cmp(&b[d]).unwrap());
+      let d = split_dim(below);
+      let partition = below.select_nth_unstable_by(med,
+        |(a, _), (b, _)| cmp_coord(a[d], b[d]));
       Some(Box::new(KDNode::from(partition, d)))
     };
     let l = if above.is_empty() { None } else {
       let med = (above.len()-1)/2;
-      let d = crate::point::variances(above)
-        .iter().enumerate().min_by(|(_,a),(_,b)| a.partial_cmp(&b).unwrap()).unwrap().0;
-      let partition = above.partition_at_index_by(med, |a, b| a[d].partial_cmp(&b[d]).unwrap());
+      let d = split_dim(above);
+      let partition = above.select_nth_unstable_by(med,
+        |(a, _), (b, _)| cmp_coord(a[d], b[d]));
       Some(Box::new(KDNode::from(partition, d)))
     };
     KDNode{
-      item: median.clone(),
-      cmp_dim: cmp_dim,
-      l: l, r: r,
+      item: median.0,
+      val: median.1.clone(),
+      cmp_dim,
+      l, r,
     }
   }
-  fn add(&mut self, v: Point) {
+  pub fn item(&self) -> (&Point<N, S>, &T) { (&self.item, &self.val) }
+  fn add(&mut self, v: Point<N, S>, val: T) {
     let item = match self.item[self.cmp_dim].partial_cmp(&v[self.cmp_dim]) {
       Some(Ordering::Greater) => &mut self.r,
       Some(Ordering::Less) => &mut self.l,
@@ -117,14 +177,14 @@ impl KDNode {
       // I realize this isn't really random but it can't be counted on
       _ => if (self.cmp_dim % 2) == 0 { &mut self.l } else { &mut self.r },
     };
-    let next_dim = (self.cmp_dim + 1) % v.len();
+    let next_dim = (self.cmp_dim + 1) % N;
     match item {
-      None => assert!(item.replace(Box::new(KDNode::new(v, next_dim))).is_none()),
-      Some(ref mut r) => r.add(v),
+      None => assert!(item.replace(Box::new(KDNode::new(v, val, next_dim))).is_none()),
+      Some(ref mut r) => r.add(v, val),
     };
   }
   fn is_leaf(&self) -> bool { self.l.is_none() && self.r.is_none() }
-  fn contains(&self, v: &Point) -> bool {
+  fn contains(&self, v: &Point<N, S>) -> bool {
     if &self.item == v { return true };
     match &self.item[self.cmp_dim].partial_cmp(&v[self.cmp_dim]) {
       Some(Ordering::Greater) => &self.r,
@@ -135,56 +195,59 @@ impl KDNode {
     }.as_ref()
     .map_or(false, |c| c.contains(v))
   }
-  fn remove(&mut self, v: &Point) -> bool {
+  // removes some descendant of this node at v, returning its value
+  fn remove(&mut self, v: &Point<N, S>) -> Option<T> {
     let (next, is_r) = match self.item[self.cmp_dim].partial_cmp(&v[self.cmp_dim]) {
-      Some(Ordering::Greater) => if self.r.is_none() { return false }
+      Some(Ordering::Greater) => if self.r.is_none() { return None }
         else { (self.r.as_mut(), true) },
-      Some(Ordering::Less) => if self.l.is_none() { return false }
+      Some(Ordering::Less) => if self.l.is_none() { return None }
         else { (self.l.as_mut(), false) },
       _ => if self.r.as_ref().map_or(false, |r| &r.item == v) { (self.r.as_mut(), true) }
         else if self.l.as_ref().map_or(false, |l| &l.item == v) { (self.l.as_mut(), false) }
-        else if self.r.as_mut().map_or(false, |r| r.remove(v)) { return true }
-        else if self.l.as_mut().map_or(false, |l| l.remove(v)) { return true }
-        else { return false },
+        else if let Some(val) = self.r.as_mut().and_then(|r| r.remove(v)) { return Some(val) }
+        else { return self.l.as_mut().and_then(|l| l.remove(v)) },
     };
     let next = next.unwrap();
     if &next.item != v { return next.remove(v) };
-    let cmp_dim = next.cmp_dim;
-    () == if let Some(r_max) = next.r.as_mut().map(|r| r.find_max(cmp_dim).clone()) {
-      assert!(next.remove(&r_max));
-      next.item = r_max;
-    } else if let Some(l_min) = next.l.as_mut().map(|l| l.find_min(cmp_dim).clone()) {
-      assert!(next.remove(&l_min));
-      next.item = l_min;
-    } else if is_r { assert!(self.r.take().unwrap().is_leaf()) }
-    else { assert!(self.l.take().unwrap().is_leaf()) }
-  }
-  fn find_min(&self, d: usize) -> &Point {
+    match next.pop_item() {
+      Some(val) => Some(val),
+      None => {
+        let leaf = if is_r { self.r.take() } else { self.l.take() }.unwrap();
+        assert!(leaf.is_leaf());
+        Some(leaf.val)
+      },
+    }
+  }
+  // replaces this node's item with one of its descendants and returns the old value,
+  // or returns None if this is a leaf and must be removed by its parent
+  fn pop_item(&mut self) -> Option<T> {
+    let cmp_dim = self.cmp_dim;
+    let next = match &self.r {
+      Some(r) => r.find_max(cmp_dim).item,
+      None => self.l.as_ref()?.find_min(cmp_dim).item,
+    };
+    let val = self.remove(&next).unwrap();
+    self.item = next;
+    Some(std::mem::replace(&mut self.val, val))
+  }
+  fn find_min(&self, d: usize) -> &Self {
     let l = self.l.as_ref().filter(|_| self.cmp_dim != d).map(|l| l.find_min(d));
     let r = self.r.as_ref().map(|r| r.find_min(d));
-    match (r, l) {
-      (None, None) => &self.item,
-      (Some(v), None) | (None, Some(v)) => if self.item[d] < v[d] { &self.item }
-        else { v },
-      (Some(r), Some(l)) => if r[d] < l[d] { r }
-        else if self.item[d] < l[d] { &self.item }
-        else { l },
-    }
+    r.into_iter().chain(l).fold(self, |m, v| if v.item[d] < m.item[d] { v } else { m })
   }
-  fn find_max(&self, d: usize) -> &Point {
+  fn find_max(&self, d: usize) -> &Self {
     let r = self.r.as_ref().filter(|_| self.cmp_dim != d).map(|r| r.find_max(d));
     let l = self.l.as_ref().map(|l| l.find_max(d));
-    match (r, l) {
-      (None, None) => &self.item,
-      (Some(v), None) | (None, Some(v)) => if self.item[d] > v[d] { &self.item }
-        else { v },
-      (Some(r), Some(l)) => if r[d] > l[d] { r }
-        else if self.item[d] > l[d] { &self.item }
-        else { l },
-    }
+    r.into_iter().chain(l).fold(self, |m, v| if v.item[d] > m.item[d] { v } else { m })
+  }
+  // squared distance from v to the plane this node splits on
+  fn plane_dist_sq(&self, v: &Point<N, S>) -> f64 {
+    let diff = self.item[self.cmp_dim].to_f64() - v[self.cmp_dim].to_f64();
+    diff * diff
   }
-  fn nearest(&self, v: &Point) -> (&Point, f32) {
-    let self_dist = self.item.dist(v);
+  // returns the closest node and its squared distance
+  fn nearest(&self, v: &Point<N, S>) -> (&Self, f64) {
+    let self_dist = self.item.dist_sq(v);
     let (close, far) = match self.item[self.cmp_dim].partial_cmp(&v[self.cmp_dim]) {
       Some(Ordering::Greater) => (&self.r, &self.l),
       Some(Ordering::Less) | _ => (&self.l, &self.r),
@@ -192,16 +255,52 @@ impl KDNode {
     let (near_pt, near_dist) = close.as_ref()
       .map(|child| child.nearest(v))
       .filter(|&(_, dist)| dist < self_dist)
-      .unwrap_or_else(|| (&self.item, self_dist));
+      .unwrap_or((self, self_dist));
     far.as_ref()
       // only check other side if current item is within hypersphere
-      .filter(|_| (self.item[self.cmp_dim] - v[self.cmp_dim]).abs() < near_dist)
+      .filter(|_| self.plane_dist_sq(v) < near_dist)
       .map(|far_side| far_side.nearest(v))
       .filter(|&(_, dist)| dist < near_dist)
       .unwrap_or((near_pt, near_dist))
   }
-  pub fn range<'a>(&self, b: &BoundingBox, buf: &mut Vec<Point>) {
-    if b.contains(&self.item) { buf.push(self.item.clone()); }
+  // keeps the k closest seen so far in buf, which is a max-heap on squared distance
+  fn k_nearest<'a>(&'a self, v: &Point<N, S>, k: usize,
+    buf: &mut BinaryHeap<Candidate<'a, T, N, S>>) {
+    let (close, far) = match self.item[self.cmp_dim].partial_cmp(&v[self.cmp_dim]) {
+      Some(Ordering::Greater) => (&self.r, &self.l),
+      _ => (&self.l, &self.r),
+    };
+    if let Some(child) = close { child.k_nearest(v, k, buf) };
+    let self_dist = self.item.dist_sq(v);
+    if buf.len() < k { buf.push(Candidate(self_dist, self)) }
+    else if buf.peek().is_some_and(|worst| self_dist < worst.0) {
+      buf.pop();
+      buf.push(Candidate(self_dist, self));
+    }
+    if let Some(far_side) = far {
+      // same as nearest, but the hypersphere is bounded by the kth closest
+      if buf.len() < k || self.plane_dist_sq(v) < buf.peek().unwrap().0 {
+        far_side.k_nearest(v, k, buf)
+      }
+    }
+  }
+  // pushes every descendant within the squared radius along with its squared distance
+  fn within_radius<'a>(&'a self, v: &Point<N, S>, r_sq: f64,
+    buf: &mut Vec<(&'a Point<N, S>, &'a T, f64)>) {
+    let self_dist = self.item.dist_sq(v);
+    if self_dist <= r_sq { buf.push((&self.item, &self.val, self_dist)) };
+    let (close, far) = match self.item[self.cmp_dim].partial_cmp(&v[self.cmp_dim]) {
+      Some(Ordering::Greater) => (&self.r, &self.l),
+      _ => (&self.l, &self.r),
+    };
+    if let Some(child) = close { child.within_radius(v, r_sq, buf) };
+    if let Some(far_side) = far {
+      // only check other side if the split plane intersects the hypersphere
+      if self.plane_dist_sq(v) <= r_sq { far_side.within_radius(v, r_sq, buf) }
+    }
+  }
+  pub fn range<'a>(&'a self, b: &BoundingBox<N, S>, buf: &mut Vec<(&'a Point<N, S>, &'a T)>) {
+    if b.contains(&self.item) { buf.push((&self.item, &self.val)); }
     let d = self.cmp_dim;
     match (self.item[d].partial_cmp(&b.min_on(d)), self.item[d].partial_cmp(&b.max_on(d))) {
       (Some(Ordering::Greater), Some(Ordering::Greater)) => {
@@ -216,10 +315,7 @@ impl KDNode {
       },
     };
   }
-  pub fn children(&self) -> std::iter::Chain<
-      std::option::Iter<Box<KDNode>>,
-      std::option::Iter<Box<KDNode>>
-    > {
+  pub fn children(&self) -> Children<'_, T, N, S> {
     self.l.iter().chain(self.r.iter())
   }
 
@@ -250,23 +346,38 @@ impl KDNode {
   }
 }
 
+// Distance paired with a node, ordered by distance so it can be kept in a heap
+#[derive(Debug)]
+struct Candidate<'a, T, const N: usize, S>(f64, &'a KDNode<T, N, S>);
+
+impl<T, const N: usize, S: Scalar> PartialEq for Candidate<'_, T, N, S> {
+  fn eq(&self, o: &Self) -> bool { self.cmp(o) == Ordering::Equal }
+}
+impl<T, const N: usize, S: Scalar> Eq for Candidate<'_, T, N, S> {}
+impl<T, const N: usize, S: Scalar> PartialOrd for Candidate<'_, T, N, S> {
+  fn partial_cmp(&self, o: &Self) -> Option<Ordering> { Some(self.cmp(o)) }
+}
+impl<T, const N: usize, S: Scalar> Ord for Candidate<'_, T, N, S> {
+  fn cmp(&self, o: &Self) -> Ordering { self.0.total_cmp(&o.0) }
+}
+
 #[cfg(test)]
 mod kdtree_test {
   use crate::kdtree::KDTree;
   use crate::point::Point;
   use crate::test_util::BadRand;
-  fn naive_nearest<'a>(v: &'a Vec<Point>, o: &Point) -> &'a Point {
+  fn naive_nearest<'a>(v: &'a [Point<3>], o: &Point<3>) -> &'a Point<3> {
     assert!(!v.is_empty());
     v.iter()
       .map(|v| (v, v.dist(o)))
       .min_by(|(_, d), (_, o_d)| d.partial_cmp(&o_d).unwrap())
       .unwrap().0
   }
-  fn naive_max<'a>(v: &'a Vec<Point>, d: usize) -> &'a Point {
+  fn naive_max(v: &[Point<3>], d: usize) -> &Point<3> {
     assert!(!v.is_empty());
     v.iter().max_by(|a, b| a[d].partial_cmp(&b[d]).unwrap()).unwrap()
   }
-  fn naive_min<'a>(v: &'a Vec<Point>, d: usize) -> &'a Point {
+  fn naive_min(v: &[Point<3>], d: usize) -> &Point<3> {
     assert!(!v.is_empty());
     v.iter().min_by(|a, b| a[d].partial_cmp(&b[d]).unwrap()).unwrap()
   }
@@ -276,28 +387,32 @@ mod kdtree_test {
     let items: Vec<_> = vec!((2.,3.), (3.,2.), (1.,1.5), (1.,2.)).iter()
       .map(|&(a,b)| Point::from((a,b,0.)))
       .collect();
-    items.iter().for_each(|p| t.add(p.clone()));
+    items.iter().enumerate().for_each(|(i, p)| t.add(*p, i));
     assert_eq!(t.size(), 4);
     assert!(t.is_valid());
-    assert_eq!(t.nearest(&Point::from(&vec!(3.,2.))), Some(&Point::from(&vec!(3., 2.))));
-    assert_eq!(t.nearest(&Point::from(&vec!(0.,0.))), Some(&Point::from(&vec!(1.,1.5))));
-    assert_eq!(t.root.as_ref().map(|r| r.find_min(0)[0]), Some(1.));
-    assert_eq!(t.root.as_ref().map(|r| r.find_max(0)[0]), Some(3.));
-    assert_eq!(t.find_max(0), Some(&Point::from((3.,2., 0.))));
-    assert!(t.find_min(0).unwrap()[0] == 1.);
+    assert_eq!(t.nearest(&Point::from(&vec!(3.,2.))), Some((&Point::from(&vec!(3., 2.)), &1)));
+    assert_eq!(t.nearest(&Point::from(&vec!(0.,0.))), Some((&Point::from(&vec!(1.,1.5)), &2)));
+    assert_eq!(t.root.as_ref().map(|r| r.find_min(0).item[0]), Some(1.));
+    assert_eq!(t.root.as_ref().map(|r| r.find_max(0).item[0]), Some(3.));
+    assert_eq!(t.find_max(0), Some((&Point::from((3.,2., 0.)), &1)));
+    assert!(t.find_min(0).unwrap().0[0] == 1.);
     (-4..4).for_each(|x|
       (-4..4).for_each(|y| {
         let p = Point::from((x as f32, y as f32, 0.));
-        assert_eq!(Some(naive_nearest(&items, &p)), t.nearest(&p));
+        assert_eq!(Some(naive_nearest(&items, &p)), t.nearest(&p).map(|(p, _)| p));
       }));
     assert!(t.is_valid());
     assert_eq!(t.size, 4);
+    let mut iterated: Vec<_> = t.iter().map(|(p, &i)| (i, *p)).collect();
+    iterated.sort_by_key(|&(i, _)| i);
+    assert_eq!(iterated, items.iter().cloned().enumerate().collect::<Vec<_>>());
 
     items.iter().enumerate().for_each(|(i, p)| {
-      assert!(t.remove(p));
+      assert_eq!(t.remove(p), Some(i));
       assert_eq!(t.size, 4-i-1);
       assert!(t.is_valid());
     });
+    assert_eq!(t.remove(&items[0]), None);
   }
   #[test]
   fn gen_test() {
@@ -305,32 +420,158 @@ mod kdtree_test {
     let mut r = BadRand::new();
     let cap = 20;
     let num_points = 256;
-    let mut points : Vec<_> = (0..num_points)
+    let points : Vec<_> = (0..num_points)
       .map(|_| Point::from((r.i64(cap) as f32, r.i64(cap) as f32, r.i64(cap) as f32)))
       .collect();
-    points.iter().for_each(|p| t.add(p.clone()));
+    points.iter().for_each(|p| t.add(*p, ()));
     assert!(t.is_valid());
     assert_eq!(num_points, t.root.as_ref().unwrap().count());
     assert_eq!(num_points, t.size);
+    assert_eq!(num_points, t.iter().count());
     assert!(points.iter().all(|p| t.contains(p)));
     (0..256).for_each(|_| {
       let p = Point::from((r.i64(cap) as f32, r.i64(cap) as f32, r.i64(cap) as f32));
       let d1 = naive_nearest(&points, &p).dist(&p);
-      let d2 = t.nearest(&p).unwrap().dist(&p);
+      let d2 = t.nearest(&p).unwrap().0.dist(&p);
       assert_eq!(d1, d2);
     });
     (0..3).for_each(|d| {
-      assert_eq!(naive_min(&points, d)[d], t.find_min(d).unwrap()[d]);
-      assert_eq!(naive_max(&points, d)[d], t.find_max(d).unwrap()[d]);
+      assert_eq!(naive_min(&points, d)[d], t.find_min(d).unwrap().0[d]);
+      assert_eq!(naive_max(&points, d)[d], t.find_max(d).unwrap().0[d]);
     });
     assert!(t.is_valid());
-    let mut from = KDTree::from(points.as_mut_slice());
+    let mut with_index: Vec<_> = points.iter().cloned().enumerate().map(|(i, p)| (p, i)).collect();
+    let mut from = KDTree::from(with_index.as_mut_slice());
     assert!(t.depth() >= from.depth());
     assert!(from.is_valid());
     points.iter().for_each(|p| {
-      assert!(from.remove(p));
+      let i = from.remove(p).unwrap();
+      assert_eq!(&with_index.iter().find(|&&(_, j)| i == j).unwrap().0, p);
       assert!(from.is_valid());
     });
+    assert!(from.is_empty());
+  }
+  #[test]
+  fn k_nearest_test() {
+    let mut r = BadRand::new();
+    let cap = 20;
+    let points : Vec<_> = (0..256)
+      .map(|_| Point::from((r.i64(cap) as f32, r.i64(cap) as f32, r.i64(cap) as f32)))
+      .collect();
+    let mut t = KDTree::new();
+    points.iter().enumerate().for_each(|(i, p)| t.add(*p, i));
+    assert!(t.k_nearest(&Point::from(0.), 0).is_empty());
+    assert_eq!(t.k_nearest(&Point::from(0.), 1000).len(), points.len());
+    (0..64).for_each(|_| {
+      let p = Point::from((r.i64(cap) as f32, r.i64(cap) as f32, r.i64(cap) as f32));
+      let k = 1 + r.i64(10).unsigned_abs() as usize;
+      let mut naive : Vec<_> = points.iter().map(|o| o.dist(&p)).collect();
+      naive.sort_by(|a, b| a.partial_cmp(b).unwrap());
+      let found = t.k_nearest(&p, k);
+      assert_eq!(found.len(), k);
+      assert!(found.iter().all(|&(o, &i, d)| o.dist(&p) == d && &points[i] == o));
+      assert_eq!(found.iter().map(|&(_, _, d)| d).collect::<Vec<_>>(), naive[..k].to_vec());
+      assert_eq!(found[0].2, t.nearest(&p).unwrap().0.dist(&p));
+    });
+  }
+  #[test]
+  fn within_radius_test() {
+    let mut r = BadRand::new();
+    let cap = 20;
+    let points : Vec<_> = (0..256)
+      .map(|_| Point::from((r.i64(cap) as f32, r.i64(cap) as f32, r.i64(cap) as f32)))
+      .collect();
+    let mut with_index: Vec<_> = points.iter().cloned().enumerate().map(|(i, p)| (p, i)).collect();
+    let t = KDTree::from(with_index.as_mut_slice());
+    assert!(KDTree::<(), 3>::new().within_radius(&Point::from(0.), 5.).is_empty());
+    (0..64).for_each(|_| {
+      let p = Point::from((r.i64(cap) as f32, r.i64(cap) as f32, r.i64(cap) as f32));
+      let rad = r.i64(8) as f32;
+      let mut naive : Vec<_> = points.iter().map(|o| o.dist(&p)).filter(|&d| d <= rad).collect();
+      naive.sort_by(|a, b| a.partial_cmp(b).unwrap());
+      let found = t.within_radius(&p, rad);
+      assert_eq!(found.len(), naive.len());
+      assert!(found.iter().all(|&(o, &i, d)| o.dist(&p) == d && d <= rad && &points[i] == o));
+      let sorted = t.within_radius_sorted(&p, rad);
+      assert_eq!(sorted.iter().map(|&(_, _, d)| d).collect::<Vec<_>>(), naive);
+    });
+  }
+  #[test]
+  fn split_test() {
+    // spread along y far more than along x or z
+    let mut pts: Vec<_> = (0..64)
+      .map(|i| (Point::from(((i % 2) as f32, i as f32 * 10., 0.)), i))
+      .collect();
+    let t = KDTree::from(pts.as_mut_slice());
+    assert_eq!(t.root.as_ref().unwrap().cmp_dim, 1);
+    assert!(t.is_valid());
+    // a NaN coordinate is ordered rather than panicking
+    pts[3].0[0] = f32::NAN;
+    assert_eq!(KDTree::from(pts.as_mut_slice()).size(), 64);
+  }
+
+  #[test]
+  fn other_dims_test() {
+    let mut r = BadRand::new();
+    let cap = 20;
+    let flat: Vec<Point<2>> = (0..128)
+      .map(|_| Point::from((r.i64(cap) as f32, r.i64(cap) as f32)))
+      .collect();
+    let mut t = KDTree::new();
+    flat.iter().for_each(|p| t.add(*p, ()));
+    assert!(t.is_valid());
+    let high: Vec<Point<6>> = (0..128)
+      .map(|_| (0..6).map(|_| r.i64(cap) as f32).collect())
+      .collect();
+    let mut with_unit: Vec<_> = high.iter().map(|p| (*p, ())).collect();
+    let h = KDTree::from(with_unit.as_mut_slice());
+    assert!(h.is_valid());
+    (0..64).for_each(|_| {
+      let p = Point::from((r.i64(cap) as f32, r.i64(cap) as f32));
+      let naive = flat.iter().map(|o| o.dist(&p)).fold(f32::INFINITY, f32::min);
+      assert_eq!(t.nearest(&p).unwrap().0.dist(&p), naive);
+      let p: Point<6> = (0..6).map(|_| r.i64(cap) as f32).collect();
+      let naive = high.iter().map(|o| o.dist(&p)).fold(f32::INFINITY, f32::min);
+      assert_eq!(h.nearest(&p).unwrap().0.dist(&p), naive);
+      assert_eq!(h.k_nearest(&p, 3)[0].2, naive);
+    });
+  }
+
+  #[test]
+  fn scalar_test() {
+    let mut r = BadRand::new();
+    let cap = 1000;
+    // offsets which would lose precision in f32
+    let offset = 4_000_000i64;
+    let points : Vec<Point<3, i64>> = (0..256)
+      .map(|_| Point::from((offset + r.i64(cap), offset + r.i64(cap), r.i64(cap))))
+      .collect();
+    let mut t = KDTree::new();
+    points.iter().enumerate().for_each(|(i, p)| t.add(*p, i));
+    let mut ft = KDTree::new();
+    points.iter().enumerate().for_each(|(i, p)| ft.add(p.cast::<f64>(), i));
+    assert!(t.is_valid() && ft.is_valid());
+    (0..64).for_each(|_| {
+      let p = Point::from((offset + r.i64(cap), offset + r.i64(cap), r.i64(cap)));
+      let naive = points.iter().map(|o| o.dist_sq(&p)).fold(f64::INFINITY, f64::min);
+      assert_eq!(t.nearest(&p).unwrap().0.dist_sq(&p), naive);
+      assert_eq!(ft.nearest(&p.cast()).unwrap().0.dist_sq(&p.cast()), naive);
+      let rad = r.i64(200);
+      let count = points.iter().filter(|o| o.dist_sq(&p) <= (rad * rad) as f64).count();
+      assert_eq!(t.within_radius(&p, rad).len(), count);
+      assert_eq!(ft.within_radius(&p.cast(), rad as f64).len(), count);
+    });
+    // squared distances which overflow i32
+    let mut wide = KDTree::<usize, 2, i32>::new();
+    wide.add(Point::from((0, 0)), 0);
+    wide.add(Point::from((100_000, 0)), 1);
+    wide.add(Point::from((-100_000, 60_000)), 2);
+    assert_eq!(wide.nearest(&Point::from((90_000, 0))).map(|(_, &i)| i), Some(1));
+    let found: Vec<_> = wide.k_nearest(&Point::default(), 3).into_iter()
+      .map(|(_, &i, d)| (i, d))
+      .collect();
+    assert_eq!(found, vec!((0, 0), (1, 100_000), (2, 116_619)));
+    assert_eq!(wide.within_radius(&Point::default(), 100_000).len(), 2);
   }
 
   // TODO add tests for range
@@ -0,0 +1,369 @@
+use crate::{
+  point::Point,
+  error::{Error, ErrorKind, Result},
+};
+use std::{
+  convert::TryInto,
+  io::{BufReader, BufRead},
+  path::Path,
+};
+
+// Public header block of a LAS file
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Header {
+  pub file_source_id: u16,
+  pub global_encoding: u16,
+  pub guid: [u8; 16],
+  pub version: (u8, u8),
+  pub system_identifier: String,
+  pub generating_software: String,
+  pub creation_day: u16,
+  pub creation_year: u16,
+  pub header_size: u16,
+  pub offset_to_points: u32,
+  pub num_vlrs: u32,
+  pub point_format: u8,
+  pub point_record_len: u16,
+  pub num_points: u64,
+  // number of points by return number, from the first return
+  pub num_points_by_return: Vec<u64>,
+  pub scale: [f64; 3],
+  pub offset: [f64; 3],
+  pub min: [f64; 3],
+  pub max: [f64; 3],
+}
+
+// Variable length record which follows the header
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vlr {
+  pub user_id: String,
+  pub record_id: u16,
+  pub description: String,
+  pub data: Vec<u8>,
+}
+
+// Attributes of a point besides its position
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Record {
+  pub intensity: u16,
+  pub return_number: u8,
+  pub number_of_returns: u8,
+  pub classification: u8,
+  // synthetic, key-point and withheld bits, followed by overlap in formats 6 to 8
+  pub classification_flags: u8,
+  pub scanner_channel: u8,
+  pub scan_direction: bool,
+  pub edge_of_flight_line: bool,
+  // in degrees
+  pub scan_angle: f32,
+  pub user_data: u8,
+  pub point_source_id: u16,
+  pub gps_time: Option<f64>,
+  pub color: Option<[u16; 3]>,
+  pub nir: Option<u16>,
+}
+
+// Contents of a LAS file, where the scale and offset have been applied to each point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Las {
+  pub header: Header,
+  pub vlrs: Vec<Vlr>,
+  pub points: Vec<(Point<3, f64>, Record)>,
+}
+
+const MIN_HEADER_SIZE: usize = 227;
+const VLR_HEADER_SIZE: usize = 54;
+
+// Reads little endian values from the front of a slice
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Bytes<'a> {
+  fn take(&mut self, n: usize) -> &'a [u8] {
+    let (front, rest) = self.0.split_at(n);
+    self.0 = rest;
+    front
+  }
+  fn u8(&mut self) -> u8 { self.take(1)[0] }
+  fn u16(&mut self) -> u16 { u16::from_le_bytes(self.take(2).try_into().unwrap()) }
+  fn u32(&mut self) -> u32 { u32::from_le_bytes(self.take(4).try_into().unwrap()) }
+  fn u64(&mut self) -> u64 { u64::from_le_bytes(self.take(8).try_into().unwrap()) }
+  fn i32(&mut self) -> i32 { i32::from_le_bytes(self.take(4).try_into().unwrap()) }
+  fn f64(&mut self) -> f64 { f64::from_le_bytes(self.take(8).try_into().unwrap()) }
+  fn f64s(&mut self) -> [f64; 3] { [self.f64(), self.f64(), self.f64()] }
+  // reads a fixed length string, which is padded with nulls
+  fn string(&mut self, n: usize) -> String {
+    let b = self.take(n);
+    let end = b.iter().position(|&c| c == 0).unwrap_or(n);
+    String::from_utf8_lossy(&b[..end]).into_owned()
+  }
+}
+
+// returns the length of a record in the point format, or None if it is not supported
+fn record_len(format: u8) -> Option<usize> {
+  Some(match format {
+    0 => 20,
+    1 => 28,
+    2 => 26,
+    3 => 34,
+    6 => 30,
+    7 => 36,
+    8 => 38,
+    _ => return None,
+  })
+}
+
+impl Header {
+  fn parse(b: &[u8]) -> Result<Self> {
+    let invalid = |s: &str| Error::Invalid(ErrorKind::Malformed(s.to_string()));
+    let mut b = Bytes(b);
+    if b.take(4) != b"LASF" { return Err(invalid("missing LASF signature")) };
+    let mut h = Header{
+      file_source_id: b.u16(),
+      global_encoding: b.u16(),
+      guid: b.take(16).try_into().unwrap(),
+      version: (b.u8(), b.u8()),
+      system_identifier: b.string(32),
+      generating_software: b.string(32),
+      creation_day: b.u16(),
+      creation_year: b.u16(),
+      header_size: b.u16(),
+      offset_to_points: b.u32(),
+      num_vlrs: b.u32(),
+      point_format: b.u8(),
+      point_record_len: b.u16(),
+      num_points: b.u32() as u64,
+      num_points_by_return: (0..5).map(|_| b.u32() as u64).collect(),
+      scale: b.f64s(),
+      offset: b.f64s(),
+      ..Default::default()
+    };
+    // bounds are stored as max then min of each axis
+    for i in 0..3 {
+      h.max[i] = b.f64();
+      h.min[i] = b.f64();
+    }
+    if h.version.0 != 1 || h.version.1 > 4 {
+      let (major, minor) = h.version;
+      return Err(Error::Invalid(ErrorKind::Unsupported(format!("version {}.{}", major, minor))))
+    }
+    // 1.4 adds 64 bit point counts after the waveform and extended record offsets
+    if h.version.1 == 4 && b.0.len() >= 148 {
+      b.take(8 + 8 + 4);
+      let num_points = b.u64();
+      let by_return: Vec<_> = (0..15).map(|_| b.u64()).collect();
+      if h.num_points == 0 { h.num_points = num_points };
+      if h.num_points_by_return.iter().all(|&n| n == 0) { h.num_points_by_return = by_return };
+    }
+    if h.point_format & 0x80 != 0 {
+      return Err(Error::Invalid(ErrorKind::Unsupported("compressed points".to_string())))
+    }
+    match record_len(h.point_format) {
+      None => return Err(Error::Invalid(ErrorKind::Unsupported(
+        format!("point format {}", h.point_format)
+      ))),
+      Some(len) if (h.point_record_len as usize) < len =>
+        return Err(invalid("point record length is too short for its format")),
+      Some(_) => (),
+    };
+    Ok(h)
+  }
+  // converts a record into a point with the scale and offset applied, and its attributes
+  fn parse_point(&self, b: &[u8]) -> (Point<3, f64>, Record) {
+    let mut b = Bytes(b);
+    let xyz = [b.i32(), b.i32(), b.i32()];
+    let p = Point::from([0, 1, 2].map(|i| xyz[i] as f64 * self.scale[i] + self.offset[i]));
+    let mut r = Record{ intensity: b.u16(), ..Default::default() };
+    let format = self.point_format;
+    if format < 6 {
+      let returns = b.u8();
+      r.return_number = returns & 0b111;
+      r.number_of_returns = (returns >> 3) & 0b111;
+      r.scan_direction = returns & 0x40 != 0;
+      r.edge_of_flight_line = returns & 0x80 != 0;
+      let class = b.u8();
+      r.classification = class & 0x1f;
+      r.classification_flags = class >> 5;
+      r.scan_angle = b.u8() as i8 as f32;
+      r.user_data = b.u8();
+      r.point_source_id = b.u16();
+      if format == 1 || format == 3 { r.gps_time = Some(b.f64()) };
+    } else {
+      let returns = b.u8();
+      r.return_number = returns & 0xf;
+      r.number_of_returns = returns >> 4;
+      let flags = b.u8();
+      r.classification_flags = flags & 0xf;
+      r.scanner_channel = (flags >> 4) & 0b11;
+      r.scan_direction = flags & 0x40 != 0;
+      r.edge_of_flight_line = flags & 0x80 != 0;
+      r.classification = b.u8();
+      r.user_data = b.u8();
+      r.scan_angle = b.u16() as i16 as f32 * 0.006;
+      r.point_source_id = b.u16();
+      r.gps_time = Some(b.f64());
+    }
+    if [2, 3, 7, 8].contains(&format) { r.color = Some([b.u16(), b.u16(), b.u16()]) };
+    if format == 8 { r.nir = Some(b.u16()) };
+    (p, r)
+  }
+}
+
+impl Las {
+  pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+    Self::read(BufReader::new(std::fs::File::open(path)?))
+  }
+  // reads an uncompressed LAS file with point format 0 to 3 or 6 to 8
+  pub fn read<R: BufRead>(mut r: R) -> Result<Self> {
+    let mut buf = vec!(0; MIN_HEADER_SIZE);
+    r.read_exact(&mut buf)?;
+    let header_size = u16::from_le_bytes([buf[94], buf[95]]) as usize;
+    if header_size < MIN_HEADER_SIZE {
+      return Err(Error::Invalid(ErrorKind::Malformed("header is too short".to_string())))
+    }
+    buf.resize(header_size, 0);
+    r.read_exact(&mut buf[MIN_HEADER_SIZE..])?;
+    let header = Header::parse(&buf)?;
+    let mut read = header_size;
+    let mut vlrs = vec!();
+    for _ in 0..header.num_vlrs {
+      let mut vlr_header = [0; VLR_HEADER_SIZE];
+      r.read_exact(&mut vlr_header)?;
+      let mut b = Bytes(&vlr_header);
+      b.u16();
+      let user_id = b.string(16);
+      let record_id = b.u16();
+      let mut data = vec!(0; b.u16() as usize);
+      let description = b.string(32);
+      r.read_exact(&mut data)?;
+      read += VLR_HEADER_SIZE + data.len();
+      vlrs.push(Vlr{ user_id, record_id, description, data });
+    }
+    let offset = header.offset_to_points as usize;
+    if offset < read {
+      return Err(Error::Invalid(ErrorKind::Malformed("points overlap the header".to_string())))
+    }
+    // skips any bytes between the records and the points
+    std::io::copy(&mut std::io::Read::take(&mut r, (offset - read) as u64), &mut std::io::sink())?;
+    let mut record = vec!(0; header.point_record_len as usize);
+    let mut points = vec!();
+    for _ in 0..header.num_points {
+      r.read_exact(&mut record)?;
+      points.push(header.parse_point(&record));
+    }
+    Ok(Las{ header, vlrs, points })
+  }
+}
+
+#[cfg(test)]
+mod las_test {
+  use super::Las;
+  use crate::{
+    point::Point,
+    kdtree::KDTree,
+    error::{Error, ErrorKind},
+  };
+  // writes a header with the given version, format and point count, followed by one VLR
+  fn header(minor: u8, format: u8, record_len: u16, num_points: u32) -> Vec<u8> {
+    let header_size: u16 = match minor { 4 => 375, 3 => 235, _ => 227 };
+    let mut b = b"LASF".to_vec();
+    b.extend(&[0; 20]);
+    b.extend(&[1, minor]);
+    b.extend(b"sys\0".iter().chain(&[0; 28]));
+    b.extend(b"pcrs".iter().chain(&[0; 28]));
+    b.extend(&[0; 4]);
+    b.extend(&header_size.to_le_bytes());
+    b.extend(&(header_size as u32 + 54 + 4).to_le_bytes());
+    b.extend(&1u32.to_le_bytes());
+    b.push(format);
+    b.extend(&record_len.to_le_bytes());
+    let legacy_points = if minor == 4 { 0 } else { num_points };
+    b.extend(&legacy_points.to_le_bytes());
+    b.extend(&[0; 20]);
+    for v in &[0.01, 0.01, 0.001, 100., 200., 0., 2., 1., 3., 2., 1., 0.] {
+      b.extend(&f64::to_le_bytes(*v));
+    }
+    if minor >= 3 { b.extend(&[0; 8]) };
+    if minor == 4 {
+      b.extend(&[0; 12]);
+      b.extend(&(num_points as u64).to_le_bytes());
+      b.extend(&[0; 120]);
+    }
+    assert_eq!(b.len(), header_size as usize);
+    b.extend(&[0; 2]);
+    b.extend(b"user".iter().chain(&[0; 12]));
+    b.extend(&7u16.to_le_bytes());
+    b.extend(&4u16.to_le_bytes());
+    b.extend(b"a record".iter().chain(&[0; 24]));
+    b.extend(&[1, 2, 3, 4]);
+    b
+  }
+  #[test]
+  fn read_legacy_formats() {
+    let mut b = header(2, 3, 34, 2);
+    for &(x, class, gps) in &[(100i32, 2u8, 1.5f64), (-50, 0x86, 2.5)] {
+      b.extend(x.to_le_bytes().iter().chain(&200i32.to_le_bytes()).chain(&5i32.to_le_bytes()));
+      b.extend(&300u16.to_le_bytes());
+      // second of three returns, scanning forward
+      b.push(2 | 3 << 3 | 0x40);
+      b.extend(&[class, (-10i8) as u8, 9, 1, 0]);
+      b.extend(&gps.to_le_bytes());
+      b.extend(&[1, 0, 2, 0, 3, 0]);
+    }
+    let las = Las::read(b.as_slice()).unwrap();
+    assert_eq!(las.header.version, (1, 2));
+    assert_eq!(las.header.system_identifier, "sys");
+    assert_eq!(las.header.min, [1., 2., 0.]);
+    assert_eq!(las.vlrs[0].user_id, "user");
+    assert_eq!(las.vlrs[0].data, vec!(1, 2, 3, 4));
+    assert_eq!(las.points.len(), 2);
+    let (p, r) = &las.points[0];
+    assert_eq!(*p, Point::from((101., 202., 0.005)));
+    assert_eq!((r.intensity, r.return_number, r.number_of_returns), (300, 2, 3));
+    assert!(r.scan_direction && !r.edge_of_flight_line);
+    assert_eq!((r.classification, r.scan_angle, r.user_data), (2, -10., 9));
+    assert_eq!((r.gps_time, r.color, r.nir), (Some(1.5), Some([1, 2, 3]), None));
+    let (p, r) = &las.points[1];
+    assert_eq!(p[0], 99.5);
+    assert_eq!((r.classification, r.classification_flags), (6, 0b100));
+    let mut points = las.points.clone();
+    let tree = KDTree::from(&mut points);
+    assert_eq!(tree.nearest(&Point::from((99., 202., 0.))).unwrap().1.gps_time, Some(2.5));
+  }
+  #[test]
+  fn read_extended_formats() {
+    let mut b = header(4, 8, 40, 1);
+    b.extend(0i32.to_le_bytes().iter().chain(&0i32.to_le_bytes()).chain(&0i32.to_le_bytes()));
+    b.extend(&7u16.to_le_bytes());
+    // fifth of nine returns, overlap bit, channel 2 and edge of flight line
+    b.extend(&[5 | 9 << 4, 0b1000 | 2 << 4 | 0x80, 40, 0]);
+    b.extend(&1000i16.to_le_bytes());
+    b.extend(&3u16.to_le_bytes());
+    b.extend(&4.25f64.to_le_bytes());
+    b.extend(&[1, 0, 2, 0, 3, 0, 4, 0]);
+    // extra bytes beyond the format
+    b.extend(&[0xff, 0xff]);
+    let las = Las::read(b.as_slice()).unwrap();
+    assert_eq!(las.header.num_points, 1);
+    let (p, r) = &las.points[0];
+    assert_eq!(*p, Point::from((100., 200., 0.)));
+    assert_eq!((r.return_number, r.number_of_returns, r.scanner_channel), (5, 9, 2));
+    assert_eq!((r.classification, r.classification_flags), (40, 0b1000));
+    assert!(r.edge_of_flight_line);
+    assert!((r.scan_angle - 6.).abs() < 1e-5);
+    assert_eq!((r.point_source_id, r.gps_time), (3, Some(4.25)));
+    assert_eq!((r.color, r.nir), (Some([1, 2, 3]), Some(4)));
+  }
+  #[test]
+  fn las_errors() {
+    let unsupported = |b: Vec<u8>| match Las::read(b.as_slice()) {
+      Err(Error::Invalid(ErrorKind::Unsupported(s))) => s,
+      r => panic!("expected unsupported, got {:?}", r),
+    };
+    assert_eq!(unsupported(header(2, 4, 57, 0)), "point format 4");
+    assert_eq!(unsupported(header(2, 0x83, 34, 0)), "compressed points");
+    assert!(matches!(Las::read(header(2, 1, 20, 0).as_slice()),
+      Err(Error::Invalid(ErrorKind::Malformed(_)))));
+    assert!(matches!(Las::read(header(2, 0, 20, 1).as_slice()), Err(Error::Io(_))));
+    assert!(matches!(Las::read(&b"LASF"[..]), Err(Error::Io(_))));
+  }
+}
@@ -1,14 +1,25 @@
 #![allow(dead_code)]
-#![feature(ptr_internals, slice_partition_at_index)]
 
 pub mod point;
+pub mod scalar;
 pub mod kdtree;
 pub mod bounding_box;
 pub mod bounded;
 pub mod iters;
 pub(crate) mod util;
-// pub mod rtree;
-//pub mod mesh;
+pub mod rtree;
+pub mod mesh;
+pub mod error;
+pub mod ply;
+pub mod stl;
+pub mod pcd;
+pub mod las;
+pub mod text;
+pub mod cloud;
+pub mod normals;
+pub mod filter;
+pub mod ransac;
+pub mod cluster;
 
 #[cfg(test)]
 pub(crate) mod test_util;
@@ -1,56 +1,291 @@
-use crate::point::Point;
-use std::io::{self, BufReader, BufRead};
+use crate::{
+  point::Point,
+  error::{Error, ErrorKind, Result, ParseResult},
+};
+use std::{
+  io::{BufReader, BufRead, BufWriter, Write},
+  path::Path,
+};
 
+// Polygon mesh, where each corner of a polygon is an index into pts and optionally an index into
+// textures and normals. Indices start at 0.
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Mesh {
-  pts: Vec<Point>,
-  textures: Vec<Point>,
-  normals: Vec<Point>,
-  polygons: Vec<Vec<(usize, Option<usize>, Option<usize>)>>,
-  dims: usize,
+  pub pts: Vec<Point<3>>,
+  pub textures: Vec<Point<3>>,
+  pub normals: Vec<Point<3>>,
+  pub polygons: Vec<Vec<Corner>>,
+  // ordered by start, any polygons before the first group belong to none
+  pub groups: Vec<Group>,
+  // material libraries referenced by the mesh
+  pub mtllibs: Vec<String>,
 }
 
+// A corner of a polygon, as indices into pts, textures and normals
+pub type Corner = (usize, Option<usize>, Option<usize>);
+
+// Consecutive polygons which share an object, group names, material and smoothing group.
+// A group lasts until the start of the next.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Group {
+  pub start: usize,
+  pub object: Option<String>,
+  pub names: Vec<String>,
+  pub material: Option<String>,
+  // None if smoothing is off
+  pub smoothing: Option<u32>,
+}
+
+// Statements which are valid but have no representation in a mesh, so are skipped
+const IGNORED_OBJ: &[&str] = &[
+  "vp", "p", "l", "cstype", "deg", "bmat", "step", "curv", "curv2", "surf", "parm", "trim",
+  "hole", "scrv", "sp", "end", "con", "mg", "bevel", "c_interp", "d_interp", "lod", "maplib",
+  "usemap", "shadow_obj", "trace_obj", "ctech", "stech",
+];
+
 impl Mesh {
-  fn new(dims: usize) -> Self {
-    assert!(dims > 0);
-    Mesh{
-      pts: vec!(),
-      textures: vec!(),
-      normals: vec!(),
-      polygons: vec!(),
-      dims: dims,
-    }
+  pub fn new() -> Self { Default::default() }
+  pub fn from_obj_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+    Self::read_obj(BufReader::new(std::fs::File::open(path)?))
   }
-  fn from_obj_file(name: &str) -> io::Result<Self> {
-    let b = BufReader::new(std::fs::File::open(name)?);
-    let mut t = Self::new(3);
-    for line in b.lines() {
-      let line = line?.clone();
-      let mut tokens = line.split_whitespace();
-      match tokens.next() {
-        None => continue,
-        Some("v") => {
-          let v : Vec<_> = tokens.map(|t| t.parse::<f32>().unwrap()).collect();
-          assert!(v.len() == 3 || v.len() == 4);
-          t.pts.push(v);
-        },
-        Some("f") => {
-          let face : Vec<_> = tokens.map(|t| {
-            let mut coord = (0, None, None);
-            t.split("/")
-            .enumerate()
-            .for_each(|(i, t)| match i {
-              0 => coord.0 = t.parse::<usize>().unwrap(),
-              1 => coord.1 = Some(t.parse::<usize>().unwrap()),
-              2 => coord.2 = Some(t.parse::<usize>().unwrap()),
-              _ => panic!("Got extra token while parsing"),
-            });
-            coord
-          }).collect();
-          t.polygons.push(face);
-        },
-        Some(v) => panic!("Unimplemented parsing in obj for {}", v),
+  // reads a Wavefront OBJ file, keeping vertices, texture coordinates, normals, faces and groups
+  pub fn read_obj<R: BufRead>(r: R) -> Result<Self> {
+    let mut t = Self::new();
+    let mut lines = r.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+      let line_num = i + 1;
+      let mut line = line?;
+      // a trailing backslash continues the statement on the next line
+      while line.trim_end().ends_with('\\') {
+        let end = line.trim_end().len() - 1;
+        line.truncate(end);
+        line.push(' ');
+        match lines.next() {
+          Some((_, next)) => line.push_str(&next?),
+          None => break,
+        }
       }
+      let content = line.split('#').next().unwrap();
+      t.parse_obj_line(content).map_err(|kind| Error::parse(line_num, kind))?;
     }
     Ok(t)
   }
+  pub fn to_obj_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+    let mut w = BufWriter::new(std::fs::File::create(path)?);
+    self.write_obj(&mut w)?;
+    Ok(w.flush()?)
+  }
+  // writes a Wavefront OBJ file which reads back to this mesh, except that a group cannot
+  // drop the material of the group before it since OBJ has no statement for that
+  pub fn write_obj<W: Write>(&self, mut w: W) -> Result<()> {
+    if !self.mtllibs.is_empty() { writeln!(w, "mtllib {}", self.mtllibs.join(" "))? };
+    for (kw, pts) in &[("v", &self.pts), ("vt", &self.textures), ("vn", &self.normals)] {
+      for p in pts.iter() { writeln!(w, "{} {} {} {}", kw, p[0], p[1], p[2])? }
+    }
+    let mut groups = self.groups.iter().peekable();
+    let mut object = None;
+    for (i, poly) in self.polygons.iter().enumerate() {
+      while let Some(g) = groups.next_if(|g| g.start <= i) {
+        write_group(&mut w, g, &mut object)?;
+      }
+      write!(w, "f")?;
+      for (v, t, n) in poly {
+        match (t, n) {
+          (None, None) => write!(w, " {}", v + 1)?,
+          (Some(t), None) => write!(w, " {}/{}", v + 1, t + 1)?,
+          (None, Some(n)) => write!(w, " {}//{}", v + 1, n + 1)?,
+          (Some(t), Some(n)) => write!(w, " {}/{}/{}", v + 1, t + 1, n + 1)?,
+        }
+      }
+      writeln!(w)?;
+    }
+    // groups after the last polygon are still kept
+    for g in groups { write_group(&mut w, g, &mut object)? }
+    Ok(())
+  }
+  fn parse_obj_line(&mut self, line: &str) -> ParseResult<()> {
+    let mut tokens = line.split_whitespace();
+    let keyword = match tokens.next() {
+      None => return Ok(()),
+      Some(k) => k,
+    };
+    let args: Vec<_> = tokens.collect();
+    match keyword {
+      // w and any vertex colors after the position are dropped
+      "v" => self.pts.push(Point::from(&parse_floats(&args, 3, 7)?)),
+      "vt" => self.textures.push(Point::from(&parse_floats(&args, 1, 3)?)),
+      "vn" => self.normals.push(Point::from(&parse_floats(&args, 3, 3)?)),
+      "f" => {
+        if args.len() < 3 {
+          return Err(ErrorKind::ValueCount{ min: 3, max: usize::MAX, found: args.len() })
+        }
+        let face = args.iter().map(|a| self.parse_face_vertex(a))
+          .collect::<ParseResult<Vec<_>>>()?;
+        self.polygons.push(face);
+      },
+      "o" => {
+        let name = args.join(" ");
+        self.group_mut().object = Some(name).filter(|n| !n.is_empty());
+      },
+      "g" => self.group_mut().names = args.iter().map(|a| a.to_string()).collect(),
+      "usemtl" => {
+        if args.is_empty() { return Err(ErrorKind::ValueCount{ min: 1, max: 1, found: 0 }) };
+        self.group_mut().material = Some(args.join(" "));
+      },
+      "mtllib" => self.mtllibs.extend(args.iter().map(|a| a.to_string())),
+      "s" => {
+        let smoothing = match args.as_slice() {
+          ["off"] | ["0"] => None,
+          [v] => Some(v.parse::<u32>().map_err(|_| ErrorKind::InvalidNumber(v.to_string()))?),
+          _ => return Err(ErrorKind::ValueCount{ min: 1, max: 1, found: args.len() }),
+        };
+        self.group_mut().smoothing = smoothing;
+      },
+      k if IGNORED_OBJ.contains(&k) => (),
+      k => return Err(ErrorKind::UnknownKeyword(k.to_string())),
+    };
+    Ok(())
+  }
+  // parses one corner of a face, such as 1, 1/2, 1//3 or 1/2/3
+  fn parse_face_vertex(&self, s: &str) -> ParseResult<Corner> {
+    let parts: Vec<_> = s.split('/').collect();
+    if parts.len() > 3 {
+      return Err(ErrorKind::Malformed(format!("face vertex {:?} has too many parts", s)))
+    }
+    let index = |i: usize, len: usize| match parts.get(i) {
+      None | Some(&"") => Ok(None),
+      Some(v) => resolve_index(v, len).map(Some),
+    };
+    let v = index(0, self.pts.len())?
+      .ok_or_else(|| ErrorKind::Malformed(format!("face vertex {:?} has no position", s)))?;
+    Ok((v, index(1, self.textures.len())?, index(2, self.normals.len())?))
+  }
+  // returns the group which will contain the next polygon
+  fn group_mut(&mut self) -> &mut Group {
+    let start = self.polygons.len();
+    match self.groups.last() {
+      Some(g) if g.start == start => (),
+      Some(g) => {
+        let next = Group{ start, ..g.clone() };
+        self.groups.push(next);
+      },
+      None => self.groups.push(Group{ start, ..Default::default() }),
+    };
+    self.groups.last_mut().unwrap()
+  }
+}
+
+// writes the statements which start g, where object is the object of the previous group
+fn write_group<W: Write>(w: &mut W, g: &Group, object: &mut Option<String>) -> Result<()> {
+  if &g.object != object {
+    writeln!(w, "o {}", g.object.as_deref().unwrap_or(""))?;
+    *object = g.object.clone();
+  }
+  writeln!(w, "g {}", g.names.join(" "))?;
+  if let Some(m) = &g.material { writeln!(w, "usemtl {}", m)? };
+  match g.smoothing {
+    None => writeln!(w, "s off")?,
+    Some(s) => writeln!(w, "s {}", s)?,
+  };
+  Ok(())
+}
+
+fn parse_floats(args: &[&str], min: usize, max: usize) -> ParseResult<Vec<f32>> {
+  if args.len() < min || args.len() > max {
+    return Err(ErrorKind::ValueCount{ min, max, found: args.len() })
+  }
+  args.iter()
+    .map(|a| a.parse::<f32>().map_err(|_| ErrorKind::InvalidNumber(a.to_string())))
+    .collect()
+}
+
+// converts a 1-indexed or negative relative index into an index from 0
+fn resolve_index(s: &str, len: usize) -> ParseResult<usize> {
+  let i = s.parse::<i64>().map_err(|_| ErrorKind::InvalidNumber(s.to_string()))?;
+  let resolved = if i < 0 { len as i64 + i } else { i - 1 };
+  if i == 0 || resolved < 0 || resolved >= len as i64 {
+    return Err(ErrorKind::IndexOutOfRange(i))
+  }
+  Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod mesh_test {
+  use super::{Mesh, Group};
+  use crate::{
+    point::Point,
+    error::ErrorKind,
+    test_util::expect_parse_error,
+  };
+  const CUBE_SIDE: &str = "
+# two triangles
+mtllib a.mtl b.mtl
+o side
+v 0 0 0
+v 1 0 0
+v 1 1 0 1.0
+v 0 1 0
+vt 0 0
+vt 1 1
+vn 0 0 1
+g front sides
+usemtl red
+s 1
+f 1/1/1 2/2/1 3//1
+f -4 -2 \\
+  -1 # continued
+s off
+f 1 2 3 4
+";
+  #[test]
+  fn read_obj() {
+    let m = Mesh::read_obj(CUBE_SIDE.as_bytes()).unwrap();
+    assert_eq!(m.pts.len(), 4);
+    assert_eq!(m.pts[2], Point::from((1., 1., 0.)));
+    assert_eq!(m.textures[1], Point::from((1., 1., 0.)));
+    assert_eq!(m.normals, vec!(Point::from((0., 0., 1.))));
+    assert_eq!(m.mtllibs, vec!("a.mtl", "b.mtl"));
+    assert_eq!(m.polygons, vec!(
+      vec!((0, Some(0), Some(0)), (1, Some(1), Some(0)), (2, None, Some(0))),
+      vec!((0, None, None), (2, None, None), (3, None, None)),
+      vec!((0, None, None), (1, None, None), (2, None, None), (3, None, None)),
+    ));
+    let group = Group{
+      start: 0,
+      object: Some("side".to_string()),
+      names: vec!("front".to_string(), "sides".to_string()),
+      material: Some("red".to_string()),
+      smoothing: Some(1),
+    };
+    assert_eq!(m.groups, vec!(group.clone(), Group{ start: 2, smoothing: None, ..group }));
+  }
+  #[test]
+  fn write_obj() {
+    let m = Mesh::read_obj(CUBE_SIDE.as_bytes()).unwrap();
+    let mut buf = vec!();
+    m.write_obj(&mut buf).unwrap();
+    assert_eq!(Mesh::read_obj(buf.as_slice()).unwrap(), m);
+    let mut trailing = m.clone();
+    trailing.groups.push(Group{
+      start: 3, names: vec!("empty".to_string()), object: None, ..m.groups[1].clone()
+    });
+    trailing.groups[0].start = 1;
+    buf.clear();
+    trailing.write_obj(&mut buf).unwrap();
+    assert_eq!(Mesh::read_obj(buf.as_slice()).unwrap(), trailing);
+    let written = String::from_utf8(buf).unwrap();
+    assert!(written.starts_with("mtllib a.mtl b.mtl\nv 0 0 0\n"));
+    assert!(written.contains("\nf 1/1/1 2/2/1 3//1\n"));
+  }
+  #[test]
+  fn obj_errors() {
+    let err = |s: &str| expect_parse_error(Mesh::read_obj(s.as_bytes()));
+    assert_eq!(err("v 0 0 0\nf 1 2 3"), (2, ErrorKind::IndexOutOfRange(2)));
+    assert_eq!(err("v 0 0 0\nf 1 1 0"), (2, ErrorKind::IndexOutOfRange(0)));
+    assert_eq!(err("v 0 0 0\n\nf 1 -2 1"), (3, ErrorKind::IndexOutOfRange(-2)));
+    assert_eq!(err("v 0 a 0"), (1, ErrorKind::InvalidNumber("a".to_string())));
+    assert_eq!(err("# c\nvn 0 0"), (2, ErrorKind::ValueCount{ min: 3, max: 3, found: 2 }));
+    assert_eq!(err("v 0 0 \\\n0\nwat"), (3, ErrorKind::UnknownKeyword("wat".to_string())));
+    assert!(Mesh::read_obj("v 0 0 0\nl 1 1\nf 1 1 1".as_bytes()).is_ok());
+  }
 }
@@ -0,0 +1,237 @@
+use std::{
+  cmp::Ordering,
+  collections::BinaryHeap,
+};
+use crate::{
+  point::{Point, covariance},
+  scalar::Scalar,
+  kdtree::KDTree,
+  cloud::PointCloud,
+  util::symmetric_eigen,
+};
+
+// Which neighbours of a point are used to fit its normal, which includes the point itself if it
+// is in the tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Neighborhood<S = f32> {
+  K(usize),
+  Radius(S),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normal<const N: usize, S = f32> {
+  // unit length, with an arbitrary sign
+  pub normal: Point<N, S>,
+  // fraction of the variance of the neighbours along the normal, which is 0 for a plane and
+  // at most 1/N when they are evenly spread in every direction
+  pub curvature: S,
+}
+
+impl<S: Scalar> Neighborhood<S> {
+  pub fn find<'a, T, const N: usize>(
+    &self,
+    tree: &'a KDTree<T, N, S>,
+    p: &Point<N, S>,
+  ) -> Vec<&'a Point<N, S>> {
+    let near = match *self {
+      Neighborhood::K(k) => tree.k_nearest(p, k),
+      Neighborhood::Radius(r) => tree.within_radius(p, r),
+    };
+    near.into_iter().map(|(q, _, _)| q).collect()
+  }
+}
+
+// fits a plane through pts by PCA, returning None if there are too few points to span one or
+// they all coincide
+pub fn fit_normal<const N: usize, S: Scalar>(pts: &[Point<N, S>]) -> Option<Normal<N, S>> {
+  if pts.len() < N { return None };
+  let eig = symmetric_eigen(covariance(pts));
+  let total: f64 = eig.iter().map(|(val, _)| val.max(0.)).sum();
+  if total <= 0. { return None };
+  let (smallest, normal) = eig[0];
+  Some(Normal{
+    normal: Point::from(normal.map(S::from_f64)),
+    curvature: S::from_f64(smallest.max(0.) / total),
+  })
+}
+
+pub fn estimate_normal<T, const N: usize, S: Scalar>(
+  tree: &KDTree<T, N, S>,
+  p: &Point<N, S>,
+  n: Neighborhood<S>,
+) -> Option<Normal<N, S>> {
+  let near: Vec<_> = n.find(tree, p).into_iter().copied().collect();
+  fit_normal(&near)
+}
+
+// estimates the normal of each point from its neighbours in tree
+pub fn estimate_normals<T, const N: usize, S: Scalar>(
+  tree: &KDTree<T, N, S>,
+  pts: &[Point<N, S>],
+  n: Neighborhood<S>,
+) -> Vec<Option<Normal<N, S>>> {
+  pts.iter().map(|p| estimate_normal(tree, p, n)).collect()
+}
+
+fn dot<const N: usize, S: Scalar>(a: &Point<N, S>, b: &Point<N, S>) -> f64 {
+  (0..N).map(|d| a[d].to_f64() * b[d].to_f64()).sum()
+}
+
+fn flip<const N: usize, S: Scalar>(n: &mut Point<N, S>) { *n = n.iter().map(|v| -v).collect() }
+
+// flips each normal so that it faces viewpoint
+pub fn orient_toward<const N: usize, S: Scalar>(
+  pts: &[Point<N, S>],
+  normals: &mut [Point<N, S>],
+  viewpoint: &Point<N, S>,
+) {
+  for (p, n) in pts.iter().zip(normals.iter_mut()) {
+    let to_view: Point<N, S> = viewpoint.iter().zip(p.iter()).map(|(v, p)| v - p).collect();
+    if dot(n, &to_view) < 0. { flip(n) };
+  }
+}
+
+// Edge of the graph between neighbours, ordered so the lowest cost is popped first from a heap
+struct Edge(f64, usize, usize);
+
+impl PartialEq for Edge {
+  fn eq(&self, o: &Self) -> bool { self.cmp(o) == Ordering::Equal }
+}
+impl Eq for Edge {}
+impl PartialOrd for Edge {
+  fn partial_cmp(&self, o: &Self) -> Option<Ordering> { Some(self.cmp(o)) }
+}
+impl Ord for Edge {
+  fn cmp(&self, o: &Self) -> Ordering { o.0.partial_cmp(&self.0).unwrap_or(Ordering::Equal) }
+}
+
+// Makes the signs of normals consistent by propagating them along a minimum spanning tree of
+// the k nearest neighbour graph, where the cost of an edge is 1 - |a.b| of its normals so that
+// the orientation is carried across nearly parallel normals first. The values of tree must be
+// indices into pts and normals. Each connected part starts at its point with the greatest last
+// coordinate, whose normal is made to face toward that axis.
+pub fn orient_consistently<const N: usize, S: Scalar>(
+  tree: &KDTree<usize, N, S>,
+  pts: &[Point<N, S>],
+  normals: &mut [Point<N, S>],
+  k: usize,
+) {
+  let mut adj = vec!(vec!(); pts.len());
+  for (i, p) in pts.iter().enumerate() {
+    for (_, &j, _) in tree.k_nearest(p, k + 1) {
+      if i == j { continue };
+      adj[i].push(j);
+      adj[j].push(i);
+    }
+  }
+  let mut seeds: Vec<_> = (0..pts.len()).collect();
+  seeds.sort_by(|&a, &b| pts[b][N-1].partial_cmp(&pts[a][N-1]).unwrap_or(Ordering::Equal));
+  let mut visited = vec!(false; pts.len());
+  let mut heap = BinaryHeap::new();
+  for seed in seeds {
+    if visited[seed] { continue };
+    if normals[seed][N-1] < S::ZERO { flip(&mut normals[seed]) };
+    heap.push(Edge(0., seed, seed));
+    while let Some(Edge(_, from, to)) = heap.pop() {
+      if visited[to] { continue };
+      visited[to] = true;
+      if dot(&normals[from], &normals[to]) < 0. { flip(&mut normals[to]) };
+      for &next in &adj[to] {
+        if visited[next] { continue };
+        heap.push(Edge(1. - dot(&normals[to], &normals[next]).abs(), to, next));
+      }
+    }
+  }
+}
+
+impl<const N: usize, S: Scalar> PointCloud<N, S> {
+  // sets the normals from neighbouring points, and a curvature channel. Points without enough
+  // neighbours get a zero normal and NaN curvature.
+  pub fn estimate_normals(&mut self, n: Neighborhood<S>) {
+    let normals = estimate_normals(&self.kdtree(), &self.pts, n);
+    let curvature = normals.iter()
+      .map(|n| n.map_or(f64::NAN, |n| n.curvature.to_f64()))
+      .collect();
+    self.set_channel("curvature", curvature);
+    self.normals = Some(normals.iter()
+      .map(|n| n.map_or(Point::default(), |n| n.normal)).collect());
+  }
+  // see orient_consistently, does nothing if there are no normals
+  pub fn orient_normals(&mut self, k: usize) {
+    let tree = self.kdtree();
+    if let Some(normals) = self.normals.as_mut() {
+      orient_consistently(&tree, &self.pts, normals, k);
+    }
+  }
+  pub fn orient_normals_toward(&mut self, viewpoint: &Point<N, S>) {
+    if let Some(normals) = self.normals.as_mut() { orient_toward(&self.pts, normals, viewpoint) };
+  }
+}
+
+#[cfg(test)]
+mod normals_test {
+  use super::{Neighborhood, estimate_normals, fit_normal};
+  use crate::{
+    point::Point,
+    kdtree::KDTree,
+    cloud::PointCloud,
+  };
+  #[test]
+  fn plane() {
+    // z = x/2 sampled on a grid
+    let mut pts: Vec<_> = (0..100)
+      .map(|i| Point::<3, f64>::from(((i % 10) as f64, (i / 10) as f64, (i % 10) as f64 / 2.)))
+      .map(|p| (p, ()))
+      .collect();
+    let tree = KDTree::from(&mut pts);
+    let pts: Vec<_> = pts.into_iter().map(|(p, _)| p).collect();
+    let expected = [-1. / 5f64.sqrt(), 0., 2. / 5f64.sqrt()];
+    for n in &[Neighborhood::K(8), Neighborhood::Radius(1.5)] {
+      for normal in estimate_normals(&tree, &pts, *n) {
+        let normal = normal.unwrap();
+        let dot: f64 = (0..3).map(|i| normal.normal[i] * expected[i]).sum();
+        assert!((dot.abs() - 1.).abs() < 1e-9);
+        assert!(normal.curvature.abs() < 1e-9);
+      }
+    }
+    assert_eq!(estimate_normals(&tree, &pts[..1], Neighborhood::Radius(0.5)), vec!(None));
+  }
+  #[test]
+  fn curved() {
+    let sphere: Vec<_> = (0..200).map(|i| {
+      let (a, b) = (i as f32 * 0.7, i as f32 * 0.13);
+      Point::from((a.cos() * b.sin(), a.sin() * b.sin(), b.cos()))
+    }).collect();
+    let flat = fit_normal(&[Point::from((0., 0.)), Point::from((1., 1.)), Point::from((2., 2.))]);
+    assert_eq!(flat.unwrap().curvature, 0.);
+    assert_eq!(fit_normal(&[Point::from((1., 1.)); 3]), None);
+    let mut c = PointCloud::from(sphere);
+    c.estimate_normals(Neighborhood::K(40));
+    let curvature = c.channel("curvature").unwrap();
+    assert!(curvature.iter().all(|&k| k > 1e-4 && k <= 1. / 3.));
+    for (p, n) in c.pts.iter().zip(c.normals.as_ref().unwrap()) {
+      // sphere normals point along the radius
+      let dot: f32 = (0..3).map(|i| p[i] * n[i]).sum();
+      assert!(dot.abs() > 0.9);
+    }
+  }
+  #[test]
+  fn orientation() {
+    let sphere: Vec<_> = (0..300).map(|i| {
+      let (a, b) = (i as f64 * 0.7, i as f64 * 0.11);
+      Point::from((a.cos() * b.sin(), a.sin() * b.sin(), b.cos()))
+    }).collect();
+    let mut c = PointCloud::from(sphere);
+    c.estimate_normals(Neighborhood::K(12));
+    // scrambles the signs before orienting
+    c.normals.as_mut().unwrap().iter_mut().step_by(3).for_each(super::flip);
+    let radial = |c: &PointCloud<3, f64>| c.pts.iter()
+      .zip(c.normals.as_ref().unwrap())
+      .map(|(p, n)| super::dot(p, n))
+      .collect::<Vec<_>>();
+    c.orient_normals(8);
+    assert!(radial(&c).iter().all(|&d| d > 0.5));
+    c.orient_normals_toward(&Point::default());
+    assert!(radial(&c).iter().all(|&d| d < -0.5));
+  }
+}
@@ -0,0 +1,432 @@
+use crate::{
+  point::Point,
+  scalar::Scalar,
+  util::exact_f32,
+  error::{Error, ErrorKind, Result, ParseResult},
+};
+use std::{
+  convert::TryInto,
+  io::{BufReader, BufRead, BufWriter, Write},
+  path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  Ascii,
+  Binary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+  Int,
+  UInt,
+  Float,
+}
+
+// Field of each point, made of count values of size bytes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+  pub name: String,
+  pub size: usize,
+  pub kind: FieldType,
+  pub count: usize,
+}
+
+// Contents of a PCD v0.7 file. A cloud with height greater than 1 is organized into rows of
+// width points, like the pixels of an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pcd {
+  pub format: Format,
+  pub fields: Vec<Field>,
+  pub width: usize,
+  pub height: usize,
+  // translation then rotation quaternion as tx ty tz qw qx qy qz
+  pub viewpoint: [f64; 7],
+  // values of every point one after another, each with the count values of every field in
+  // order, so that stride() values make up a point
+  pub data: Vec<f64>,
+}
+
+const IDENTITY_VIEWPOINT: [f64; 7] = [0., 0., 0., 1., 0., 0., 0.];
+
+impl Field {
+  pub fn new(name: &str, kind: FieldType, size: usize) -> Self {
+    Field{ name: name.to_string(), size, kind, count: 1 }
+  }
+  fn type_char(&self) -> char {
+    match self.kind {
+      FieldType::Int => 'I',
+      FieldType::UInt => 'U',
+      FieldType::Float => 'F',
+    }
+  }
+  fn parse(&self, s: &str) -> ParseResult<f64> {
+    let invalid = || ErrorKind::InvalidNumber(s.to_string());
+    match self.kind {
+      FieldType::Int => s.parse::<i64>().map(|v| v as f64).map_err(|_| invalid()),
+      FieldType::UInt => s.parse::<u64>().map(|v| v as f64).map_err(|_| invalid()),
+      FieldType::Float => s.parse::<f64>().map_err(|_| invalid()),
+    }
+  }
+  fn read_bytes(&self, b: &[u8]) -> f64 {
+    match (self.kind, self.size) {
+      (FieldType::Int, 1) => b[0] as i8 as f64,
+      (FieldType::Int, 2) => i16::from_le_bytes(b.try_into().unwrap()) as f64,
+      (FieldType::Int, 4) => i32::from_le_bytes(b.try_into().unwrap()) as f64,
+      (FieldType::Int, _) => i64::from_le_bytes(b.try_into().unwrap()) as f64,
+      (FieldType::UInt, 1) => b[0] as f64,
+      (FieldType::UInt, 2) => u16::from_le_bytes(b.try_into().unwrap()) as f64,
+      (FieldType::UInt, 4) => u32::from_le_bytes(b.try_into().unwrap()) as f64,
+      (FieldType::UInt, _) => u64::from_le_bytes(b.try_into().unwrap()) as f64,
+      (FieldType::Float, 4) => f32::from_le_bytes(b.try_into().unwrap()) as f64,
+      (FieldType::Float, _) => f64::from_le_bytes(b.try_into().unwrap()),
+    }
+  }
+  fn write_binary<W: Write>(&self, w: &mut W, v: f64) -> std::io::Result<()> {
+    match (self.kind, self.size) {
+      (FieldType::Int, 1) => w.write_all(&(v as i8).to_le_bytes()),
+      (FieldType::Int, 2) => w.write_all(&(v as i16).to_le_bytes()),
+      (FieldType::Int, 4) => w.write_all(&(v as i32).to_le_bytes()),
+      (FieldType::Int, _) => w.write_all(&(v as i64).to_le_bytes()),
+      (FieldType::UInt, 1) => w.write_all(&(v as u8).to_le_bytes()),
+      (FieldType::UInt, 2) => w.write_all(&(v as u16).to_le_bytes()),
+      (FieldType::UInt, 4) => w.write_all(&(v as u32).to_le_bytes()),
+      (FieldType::UInt, _) => w.write_all(&(v as u64).to_le_bytes()),
+      (FieldType::Float, 4) => w.write_all(&(v as f32).to_le_bytes()),
+      (FieldType::Float, _) => w.write_all(&v.to_le_bytes()),
+    }
+  }
+  fn write_ascii<W: Write>(&self, w: &mut W, v: f64) -> std::io::Result<()> {
+    match (self.kind, self.size) {
+      (FieldType::Float, 4) => write!(w, "{}", v as f32),
+      (FieldType::Float, _) => write!(w, "{}", v),
+      _ => write!(w, "{}", v as i64),
+    }
+  }
+}
+
+impl Pcd {
+  pub fn new(format: Format) -> Self {
+    Pcd{ format, fields: vec!(), width: 0, height: 1, viewpoint: IDENTITY_VIEWPOINT, data: vec!() }
+  }
+  pub fn is_organized(&self) -> bool { self.height > 1 }
+  // number of values in each point
+  pub fn stride(&self) -> usize { self.fields.iter().map(|f| f.count).sum() }
+  // number of points
+  pub fn len(&self) -> usize { self.data.len().checked_div(self.stride()).unwrap_or(0) }
+  pub fn is_empty(&self) -> bool { self.len() == 0 }
+  // the values of each point, where a cloud without fields has none
+  fn rows(&self) -> std::slice::ChunksExact<'_, f64> {
+    self.data.chunks_exact(self.stride().max(1))
+  }
+  // returns the values of the point at row and col of an organized cloud
+  pub fn get(&self, row: usize, col: usize) -> Option<&[f64]> {
+    if col >= self.width { return None };
+    let (i, n) = (row * self.width + col, self.stride());
+    self.data.get(i * n..(i + 1) * n)
+  }
+  // returns where the values of the named field start in each point, and the field
+  pub fn field(&self, name: &str) -> Option<(usize, &Field)> {
+    let mut offset = 0;
+    for f in &self.fields {
+      if f.name == name { return Some((offset, f)) };
+      offset += f.count;
+    }
+    None
+  }
+  // returns the values of the named field for each point
+  pub fn values(&self, name: &str) -> Option<Vec<&[f64]>> {
+    let (offset, f) = self.field(name)?;
+    Some(self.rows().map(|d| &d[offset..offset + f.count]).collect())
+  }
+  pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+    Self::read(BufReader::new(std::fs::File::open(path)?))
+  }
+  // reads a PCD file in ascii or binary, errors in the header or ascii data are numbered by line
+  pub fn read<R: BufRead>(mut r: R) -> Result<Self> {
+    let mut t = Self::new(Format::Ascii);
+    let mut num_points = None;
+    let mut line_num = 0;
+    loop {
+      let mut line = String::new();
+      if r.read_line(&mut line)? == 0 {
+        return Err(Error::parse(line_num, ErrorKind::UnexpectedEof))
+      }
+      line_num += 1;
+      let done = t.parse_header_line(&line, &mut num_points)
+        .map_err(|kind| Error::parse(line_num, kind))?;
+      if done { break };
+    }
+    t.check_header(num_points).map_err(|kind| Error::parse(line_num, kind))?;
+    let n = t.width * t.height;
+    match t.format {
+      Format::Ascii => {
+        let mut lines = r.lines();
+        let mut read = 0;
+        while read < n {
+          let line = lines.next().ok_or_else(|| Error::parse(line_num, ErrorKind::UnexpectedEof))??;
+          line_num += 1;
+          if line.trim().is_empty() { continue };
+          t.parse_point(&line).map_err(|kind| Error::parse(line_num, kind))?;
+          read += 1;
+        }
+      },
+      Format::Binary => {
+        let point_size = t.fields.iter().map(|f| f.size * f.count).sum::<usize>();
+        let mut buf = vec!(0; point_size);
+        t.data.reserve(n * t.stride());
+        for _ in 0..n {
+          r.read_exact(&mut buf)?;
+          let mut bytes = buf.as_slice();
+          for f in t.fields.iter().flat_map(|f| (0..f.count).map(move |_| f)) {
+            let (b, rest) = bytes.split_at(f.size);
+            bytes = rest;
+            t.data.push(f.read_bytes(b));
+          }
+        }
+      },
+    };
+    Ok(t)
+  }
+  // parses a line of the header, returning true after the DATA line which ends it
+  fn parse_header_line(&mut self, line: &str, num_points: &mut Option<usize>) -> ParseResult<bool> {
+    let line = line.split('#').next().unwrap();
+    let tokens: Vec<_> = line.split_whitespace().collect();
+    let (keyword, args) = match tokens.split_first() {
+      None => return Ok(false),
+      Some((k, args)) => (*k, args),
+    };
+    let number = |s: &str| s.parse::<usize>().map_err(|_| ErrorKind::InvalidNumber(s.to_string()));
+    let one = || match args {
+      [v] => number(v),
+      _ => Err(ErrorKind::ValueCount{ min: 1, max: 1, found: args.len() }),
+    };
+    // SIZE, TYPE and COUNT must have one value for each field
+    let per_field = |fields: &[Field]| {
+      if args.len() != fields.len() {
+        return Err(ErrorKind::ValueCount{ min: fields.len(), max: fields.len(), found: args.len() })
+      }
+      Ok(())
+    };
+    match keyword {
+      "VERSION" => match args {
+        ["0.7"] | ["0.6"] | [".7"] | [".6"] => (),
+        _ => return Err(ErrorKind::Unsupported(format!("version {:?}", args.join(" ")))),
+      },
+      "FIELDS" | "COLUMNS" => self.fields = args.iter()
+        .map(|n| Field::new(n, FieldType::Float, 4))
+        .collect(),
+      "SIZE" => {
+        per_field(&self.fields)?;
+        for (f, s) in self.fields.iter_mut().zip(args) { f.size = number(s)? }
+      },
+      "TYPE" => {
+        per_field(&self.fields)?;
+        for (f, s) in self.fields.iter_mut().zip(args) {
+          f.kind = match *s {
+            "I" => FieldType::Int,
+            "U" => FieldType::UInt,
+            "F" => FieldType::Float,
+            s => return Err(ErrorKind::Unsupported(format!("field type {:?}", s))),
+          };
+        }
+      },
+      "COUNT" => {
+        per_field(&self.fields)?;
+        for (f, s) in self.fields.iter_mut().zip(args) { f.count = number(s)? }
+      },
+      "WIDTH" => self.width = one()?,
+      "HEIGHT" => self.height = one()?,
+      "POINTS" => *num_points = Some(one()?),
+      "VIEWPOINT" => {
+        if args.len() != 7 {
+          return Err(ErrorKind::ValueCount{ min: 7, max: 7, found: args.len() })
+        }
+        for (v, s) in self.viewpoint.iter_mut().zip(args) {
+          *v = s.parse().map_err(|_| ErrorKind::InvalidNumber(s.to_string()))?;
+        }
+      },
+      "DATA" => {
+        self.format = match args {
+          ["ascii"] => Format::Ascii,
+          ["binary"] => Format::Binary,
+          [f] => return Err(ErrorKind::Unsupported(format!("data {:?}", f))),
+          _ => return Err(ErrorKind::ValueCount{ min: 1, max: 1, found: args.len() }),
+        };
+        return Ok(true)
+      },
+      k => return Err(ErrorKind::UnknownKeyword(k.to_string())),
+    };
+    Ok(false)
+  }
+  fn check_header(&self, num_points: Option<usize>) -> ParseResult<()> {
+    if self.fields.is_empty() { return Err(ErrorKind::MissingProperty("FIELDS".to_string())) };
+    for f in &self.fields {
+      let valid = match f.kind {
+        FieldType::Float => f.size == 4 || f.size == 8,
+        _ => [1, 2, 4, 8].contains(&f.size),
+      };
+      if !valid {
+        return Err(ErrorKind::Unsupported(format!("size {} of field {:?}", f.size, f.name)))
+      }
+    }
+    match num_points {
+      Some(n) if n != self.width * self.height => Err(ErrorKind::Malformed(format!(
+        "{} points does not match width {} and height {}", n, self.width, self.height,
+      ))),
+      _ => Ok(()),
+    }
+  }
+  // parses the values of a point onto the end of data
+  fn parse_point(&mut self, line: &str) -> ParseResult<()> {
+    let tokens: Vec<_> = line.split_whitespace().collect();
+    let n = self.stride();
+    if tokens.len() != n {
+      return Err(ErrorKind::ValueCount{ min: n, max: n, found: tokens.len() })
+    }
+    let fields = self.fields.iter().flat_map(|f| (0..f.count).map(move |_| f));
+    for (f, s) in fields.zip(tokens) { self.data.push(f.parse(s)?) }
+    Ok(())
+  }
+  pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+    let mut w = BufWriter::new(std::fs::File::create(path)?);
+    self.write(&mut w)?;
+    Ok(w.flush()?)
+  }
+  pub fn write<W: Write>(&self, mut w: W) -> Result<()> {
+    if self.len() * self.stride() != self.data.len() {
+      return Err(Error::Invalid(ErrorKind::Malformed(format!(
+        "{} values do not make whole points of {} values", self.data.len(), self.stride(),
+      ))))
+    }
+    let join = |f: &dyn Fn(&Field) -> String| {
+      self.fields.iter().map(f).collect::<Vec<_>>().join(" ")
+    };
+    writeln!(w, "# .PCD v0.7 - Point Cloud Data file format\nVERSION 0.7")?;
+    writeln!(w, "FIELDS {}", join(&|f| f.name.clone()))?;
+    writeln!(w, "SIZE {}", join(&|f| f.size.to_string()))?;
+    writeln!(w, "TYPE {}", join(&|f| f.type_char().to_string()))?;
+    writeln!(w, "COUNT {}", join(&|f| f.count.to_string()))?;
+    writeln!(w, "WIDTH {}\nHEIGHT {}", self.width, self.height)?;
+    let vp: Vec<_> = self.viewpoint.iter().map(|v| v.to_string()).collect();
+    writeln!(w, "VIEWPOINT {}\nPOINTS {}", vp.join(" "), self.len())?;
+    match self.format {
+      Format::Ascii => writeln!(w, "DATA ascii")?,
+      Format::Binary => writeln!(w, "DATA binary")?,
+    };
+    for d in self.rows() {
+      let fields = self.fields.iter().flat_map(|f| (0..f.count).map(move |_| f));
+      for (i, (f, &v)) in fields.zip(d).enumerate() {
+        if self.format == Format::Binary {
+          f.write_binary(&mut w, v)?;
+          continue
+        }
+        if i != 0 { write!(w, " ")? };
+        f.write_ascii(&mut w, v)?;
+      }
+      if self.format == Format::Ascii { writeln!(w)? };
+    }
+    Ok(())
+  }
+  // returns the x, y and z fields of each point
+  pub fn points<S: Scalar>(&self) -> ParseResult<Vec<Point<3, S>>> {
+    let xyz = ["x", "y", "z"].iter()
+      .map(|n| self.field(n).map(|(offset, _)| offset))
+      .collect::<Option<Vec<_>>>()
+      .ok_or_else(|| ErrorKind::MissingProperty("x, y and z".to_string()))?;
+    Ok(self.rows().map(|d| xyz.iter().map(|&i| S::from_f64(d[i])).collect()).collect())
+  }
+  // creates an unorganized cloud of x, y and z fields, which are stored as floats if they can
+  // be exactly
+  pub fn from_points<S: Scalar>(pts: &[Point<3, S>], format: Format) -> Self {
+    let exact = pts.iter()
+      .flat_map(|p| p.iter())
+      .all(|v| exact_f32(v.to_f64()));
+    let size = if exact { 4 } else { 8 };
+    Pcd{
+      fields: ["x", "y", "z"].iter().map(|n| Field::new(n, FieldType::Float, size)).collect(),
+      width: pts.len(),
+      data: pts.iter().flat_map(|p| p.iter().map(|v| v.to_f64())).collect(),
+      ..Self::new(format)
+    }
+  }
+}
+
+pub fn read_points<S: Scalar, R: BufRead>(r: R) -> Result<Vec<Point<3, S>>> {
+  Pcd::read(r)?.points().map_err(Error::Invalid)
+}
+
+pub fn write_points<S: Scalar, W: Write>(w: W, pts: &[Point<3, S>], format: Format) -> Result<()> {
+  Pcd::from_points(pts, format).write(w)
+}
+
+#[cfg(test)]
+mod pcd_test {
+  use super::{Pcd, Format, FieldType};
+  use crate::{
+    point::Point,
+    error::{Error, ErrorKind},
+    test_util::expect_parse_error,
+  };
+  const ORGANIZED: &str = "# .PCD v0.7 - Point Cloud Data file format
+VERSION 0.7
+FIELDS x y z rgb intensity normal
+SIZE 4 4 4 4 2 4
+TYPE F F F U U F
+COUNT 1 1 1 1 1 3
+WIDTH 2
+HEIGHT 2
+VIEWPOINT 1 2 3 1 0 0 0
+POINTS 4
+DATA ascii
+0 0 0 4278190080 10 0 0 1
+1 0 0 0 20 0 0 1
+
+0 1 0.5 0 30 0 0 1
+1 1 0 0 40 0 0 -1
+";
+  #[test]
+  fn read_ascii() {
+    let pcd = Pcd::read(ORGANIZED.as_bytes()).unwrap();
+    assert!(pcd.is_organized());
+    assert_eq!((pcd.len(), pcd.stride()), (4, 8));
+    assert_eq!(pcd.viewpoint, [1., 2., 3., 1., 0., 0., 0.]);
+    assert_eq!(pcd.fields[4].kind, FieldType::UInt);
+    assert_eq!(pcd.get(1, 0).unwrap()[..3], [0., 1., 0.5]);
+    assert_eq!(pcd.get(0, 2), None);
+    assert_eq!(pcd.values("normal").unwrap()[3], [0., 0., -1.]);
+    assert_eq!(pcd.values("rgb").unwrap()[0], [4278190080.]);
+    assert_eq!(pcd.points::<f32>().unwrap()[2], Point::from((0., 1., 0.5)));
+  }
+  #[test]
+  fn round_trip() {
+    let pcd = Pcd::read(ORGANIZED.as_bytes()).unwrap();
+    for &format in &[Format::Ascii, Format::Binary] {
+      let mut buf = vec!();
+      Pcd{ format, ..pcd.clone() }.write(&mut buf).unwrap();
+      assert_eq!(Pcd::read(buf.as_slice()).unwrap(), Pcd{ format, ..pcd.clone() });
+    }
+    let pts = vec!(Point::<3, f64>::from((0.1, 2., 3.)), Point::from((-1e-300, 0., 1.)));
+    let mut buf = vec!();
+    super::write_points(&mut buf, &pts, Format::Binary).unwrap();
+    assert_eq!(super::read_points::<f64, _>(buf.as_slice()).unwrap(), pts);
+    let partial = Pcd{ data: vec!(0.; 12), ..pcd };
+    assert!(matches!(partial.write(vec!()), Err(Error::Invalid(ErrorKind::Malformed(_)))));
+  }
+  #[test]
+  fn pcd_errors() {
+    let err = |s: &str| expect_parse_error(Pcd::read(s.as_bytes()));
+    assert_eq!(err("FIELDS x y\nSIZE 4\n"), (2, ErrorKind::ValueCount{ min: 2, max: 2, found: 1 }));
+    assert_eq!(err("FIELDS x\nSIZE 3\nWIDTH 1\nDATA ascii\n").0, 4);
+    assert_eq!(err("FIELDS x\nWIDTH 2\nPOINTS 3\nDATA ascii\n").0, 4);
+    assert_eq!(err("FIELDS x\nDATA binary_compressed\n"),
+      (2, ErrorKind::Unsupported("data \"binary_compressed\"".to_string())));
+    assert_eq!(err("FIELDS x y\nWIDTH 1\nDATA ascii\n1\n"),
+      (4, ErrorKind::ValueCount{ min: 2, max: 2, found: 1 }));
+    assert_eq!(err("FIELDS x\nTYPE I\nWIDTH 2\nDATA ascii\n1\n1.5\n"),
+      (6, ErrorKind::InvalidNumber("1.5".to_string())));
+    assert_eq!(err("FIELDS x\nWIDTH 1\n").1, ErrorKind::UnexpectedEof);
+    let truncated = "FIELDS x\nWIDTH 1\nDATA binary\n\0\0";
+    assert!(matches!(Pcd::read(truncated.as_bytes()), Err(Error::Io(_))));
+  }
+}
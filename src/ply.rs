@@ -0,0 +1,645 @@
+use crate::{
+  point::Point,
+  scalar::Scalar,
+  mesh::Mesh,
+  util::exact_f32,
+  error::{Error, ErrorKind, Result, ParseResult},
+};
+use std::{
+  convert::TryInto,
+  io::{self, BufReader, BufRead, BufWriter, Read, Write},
+  path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  Ascii,
+  BinaryLittleEndian,
+  BinaryBigEndian,
+}
+
+// Type of a scalar property, or of the count or items of a list property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+  Char,
+  UChar,
+  Short,
+  UShort,
+  Int,
+  UInt,
+  Float,
+  Double,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyKind {
+  Scalar(Type),
+  List { count: Type, item: Type },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+  pub name: String,
+  pub kind: PropertyKind,
+}
+
+// Values of one property for every row of an element
+#[derive(Debug, Clone, PartialEq)]
+pub enum Values {
+  Scalar(Vec<f64>),
+  // the items of row i are items[offsets[i]..offsets[i+1]]
+  List { offsets: Vec<usize>, items: Vec<f64> },
+}
+
+// Element declared in the header along with its data, stored by column so that each property
+// takes a single allocation however many rows there are. values[i] holds the values of
+// properties[i] and has one entry for each of the count rows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+  pub name: String,
+  pub count: usize,
+  pub properties: Vec<Property>,
+  pub values: Vec<Values>,
+}
+
+// Contents of a PLY file. Values of every type are kept as f64, which holds all of them exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ply {
+  pub format: Format,
+  pub comments: Vec<String>,
+  pub obj_info: Vec<String>,
+  pub elements: Vec<Element>,
+}
+
+impl Type {
+  // accepts both the original names and sized names such as float32
+  pub fn from_name(s: &str) -> Option<Self> {
+    Some(match s {
+      "char" | "int8" => Type::Char,
+      "uchar" | "uint8" => Type::UChar,
+      "short" | "int16" => Type::Short,
+      "ushort" | "uint16" => Type::UShort,
+      "int" | "int32" => Type::Int,
+      "uint" | "uint32" => Type::UInt,
+      "float" | "float32" => Type::Float,
+      "double" | "float64" => Type::Double,
+      _ => return None,
+    })
+  }
+  pub fn name(self) -> &'static str {
+    match self {
+      Type::Char => "char",
+      Type::UChar => "uchar",
+      Type::Short => "short",
+      Type::UShort => "ushort",
+      Type::Int => "int",
+      Type::UInt => "uint",
+      Type::Float => "float",
+      Type::Double => "double",
+    }
+  }
+  pub fn size(self) -> usize {
+    match self {
+      Type::Char | Type::UChar => 1,
+      Type::Short | Type::UShort => 2,
+      Type::Int | Type::UInt | Type::Float => 4,
+      Type::Double => 8,
+    }
+  }
+  fn range(self) -> Option<(f64, f64)> {
+    Some(match self {
+      Type::Char => (i8::MIN as f64, i8::MAX as f64),
+      Type::UChar => (0., u8::MAX as f64),
+      Type::Short => (i16::MIN as f64, i16::MAX as f64),
+      Type::UShort => (0., u16::MAX as f64),
+      Type::Int => (i32::MIN as f64, i32::MAX as f64),
+      Type::UInt => (0., u32::MAX as f64),
+      Type::Float | Type::Double => return None,
+    })
+  }
+  fn parse(self, s: &str) -> ParseResult<f64> {
+    let invalid = || ErrorKind::InvalidNumber(s.to_string());
+    match self.range() {
+      None => s.parse::<f64>().map_err(|_| invalid()),
+      Some((min, max)) => {
+        let v = s.parse::<i64>().map_err(|_| invalid())? as f64;
+        if v < min || v > max { return Err(invalid()) };
+        Ok(v)
+      },
+    }
+  }
+  fn read_binary<R: Read>(self, r: &mut R, format: Format) -> io::Result<f64> {
+    let mut buf = [0u8; 8];
+    let buf = &mut buf[..self.size()];
+    r.read_exact(buf)?;
+    if format == Format::BinaryBigEndian { buf.reverse() };
+    Ok(match self {
+      Type::Char => buf[0] as i8 as f64,
+      Type::UChar => buf[0] as f64,
+      Type::Short => i16::from_le_bytes(buf[..].try_into().unwrap()) as f64,
+      Type::UShort => u16::from_le_bytes(buf[..].try_into().unwrap()) as f64,
+      Type::Int => i32::from_le_bytes(buf[..].try_into().unwrap()) as f64,
+      Type::UInt => u32::from_le_bytes(buf[..].try_into().unwrap()) as f64,
+      Type::Float => f32::from_le_bytes(buf[..].try_into().unwrap()) as f64,
+      Type::Double => f64::from_le_bytes(buf[..].try_into().unwrap()),
+    })
+  }
+  fn write_binary<W: Write>(self, w: &mut W, v: f64, format: Format) -> io::Result<()> {
+    let mut buf = [0u8; 8];
+    let n = self.size();
+    match self {
+      Type::Char => buf[0] = v as i8 as u8,
+      Type::UChar => buf[0] = v as u8,
+      Type::Short => buf[..n].copy_from_slice(&(v as i16).to_le_bytes()),
+      Type::UShort => buf[..n].copy_from_slice(&(v as u16).to_le_bytes()),
+      Type::Int => buf[..n].copy_from_slice(&(v as i32).to_le_bytes()),
+      Type::UInt => buf[..n].copy_from_slice(&(v as u32).to_le_bytes()),
+      Type::Float => buf[..n].copy_from_slice(&(v as f32).to_le_bytes()),
+      Type::Double => buf.copy_from_slice(&v.to_le_bytes()),
+    };
+    let buf = &mut buf[..n];
+    if format == Format::BinaryBigEndian { buf.reverse() };
+    w.write_all(buf)
+  }
+  fn write_ascii<W: Write>(self, w: &mut W, v: f64) -> io::Result<()> {
+    match self {
+      Type::Float => write!(w, "{}", v as f32),
+      Type::Double => write!(w, "{}", v),
+      _ => write!(w, "{}", v as i64),
+    }
+  }
+}
+
+impl Values {
+  fn new(kind: PropertyKind) -> Self {
+    match kind {
+      PropertyKind::Scalar(_) => Values::Scalar(vec!()),
+      PropertyKind::List{ .. } => Values::List{ offsets: vec!(0), items: vec!() },
+    }
+  }
+  pub fn len(&self) -> usize {
+    match self {
+      Values::Scalar(v) => v.len(),
+      Values::List{ offsets, .. } => offsets.len() - 1,
+    }
+  }
+  pub fn is_empty(&self) -> bool { self.len() == 0 }
+  // the values of row i, which is a single value for a scalar property
+  pub fn get(&self, i: usize) -> &[f64] {
+    match self {
+      Values::Scalar(v) => &v[i..=i],
+      Values::List{ offsets, items } => &items[offsets[i]..offsets[i+1]],
+    }
+  }
+  // adds a row to a list property
+  fn push_list<I: IntoIterator<Item = f64>>(&mut self, vals: I) {
+    if let Values::List{ offsets, items } = self {
+      items.extend(vals);
+      offsets.push(items.len());
+    }
+  }
+}
+
+impl Element {
+  pub fn new(name: &str) -> Self {
+    Element{ name: name.to_string(), count: 0, properties: vec!(), values: vec!() }
+  }
+  // adds a property with no values, which must be given one for each row
+  pub fn add_property(&mut self, name: &str, kind: PropertyKind) {
+    self.properties.push(Property{ name: name.to_string(), kind });
+    self.values.push(Values::new(kind));
+  }
+  pub fn property_index(&self, name: &str) -> Option<usize> {
+    self.properties.iter().position(|p| p.name == name)
+  }
+  // returns the value of a scalar property in each row
+  pub fn column(&self, name: &str) -> Option<&[f64]> {
+    match &self.values[self.property_index(name)?] {
+      Values::Scalar(v) => Some(v),
+      Values::List{ .. } => None,
+    }
+  }
+  fn parse_row(&mut self, line: &str) -> ParseResult<()> {
+    let mut tokens = line.split_whitespace();
+    let name = &self.name;
+    let mut next = |t: Type| match tokens.next() {
+      None => Err(ErrorKind::Malformed(format!("too few values for element {:?}", name))),
+      Some(v) => t.parse(v),
+    };
+    for (p, vals) in self.properties.iter().zip(self.values.iter_mut()) {
+      match (p.kind, vals) {
+        (PropertyKind::Scalar(t), Values::Scalar(v)) => v.push(next(t)?),
+        (PropertyKind::List{ count, item }, Values::List{ offsets, items }) => {
+          let n = next(count)?;
+          if n < 0. { return Err(ErrorKind::InvalidNumber(n.to_string())) };
+          for _ in 0..n as usize { items.push(next(item)?) }
+          offsets.push(items.len());
+        },
+        _ => unreachable!(),
+      };
+    }
+    if tokens.next().is_some() {
+      return Err(ErrorKind::Malformed(format!("too many values for element {:?}", self.name)))
+    }
+    Ok(())
+  }
+  fn read_row<R: Read>(&mut self, r: &mut R, format: Format) -> Result<()> {
+    for (p, vals) in self.properties.iter().zip(self.values.iter_mut()) {
+      match (p.kind, vals) {
+        (PropertyKind::Scalar(t), Values::Scalar(v)) => v.push(t.read_binary(r, format)?),
+        (PropertyKind::List{ count, item }, Values::List{ offsets, items }) => {
+          let n = count.read_binary(r, format)?;
+          if n < 0. { return Err(Error::Invalid(ErrorKind::InvalidNumber(n.to_string()))) };
+          for _ in 0..n as usize { items.push(item.read_binary(r, format)?) }
+          offsets.push(items.len());
+        },
+        _ => unreachable!(),
+      };
+    }
+    Ok(())
+  }
+  // checks that every property has a value of the right kind for each row
+  fn check(&self) -> Result<()> {
+    let invalid = |found| Err(Error::Invalid(ErrorKind::ValueCount{
+      min: self.count, max: self.count, found,
+    }));
+    if self.values.len() != self.properties.len() {
+      return Err(Error::Invalid(ErrorKind::ValueCount{
+        min: self.properties.len(), max: self.properties.len(), found: self.values.len(),
+      }))
+    }
+    for (p, vals) in self.properties.iter().zip(&self.values) {
+      match (p.kind, vals) {
+        (PropertyKind::Scalar(_), Values::Scalar(_)) |
+        (PropertyKind::List{ .. }, Values::List{ .. }) => {},
+        _ => return Err(Error::Invalid(ErrorKind::Malformed(
+          format!("values of property {:?} do not match its kind", p.name)))),
+      };
+      if vals.len() != self.count { return invalid(vals.len()) };
+    }
+    Ok(())
+  }
+  fn write_row<W: Write>(&self, w: &mut W, row: usize, format: Format) -> Result<()> {
+    for (i, (p, vals)) in self.properties.iter().zip(&self.values).enumerate() {
+      let vals = vals.get(row);
+      let (count, item) = match p.kind {
+        PropertyKind::Scalar(t) => (None, t),
+        PropertyKind::List{ count, item } => (Some(count), item),
+      };
+      if format == Format::Ascii {
+        if i != 0 { write!(w, " ")? };
+        if let Some(count) = count {
+          count.write_ascii(w, vals.len() as f64)?;
+          if !vals.is_empty() { write!(w, " ")? };
+        }
+        for (j, &v) in vals.iter().enumerate() {
+          if j != 0 { write!(w, " ")? };
+          item.write_ascii(w, v)?;
+        }
+      } else {
+        if let Some(count) = count { count.write_binary(w, vals.len() as f64, format)? };
+        for &v in vals { item.write_binary(w, v, format)? }
+      }
+    }
+    if format == Format::Ascii { writeln!(w)? };
+    Ok(())
+  }
+}
+
+impl Ply {
+  pub fn new(format: Format) -> Self {
+    Ply{ format, comments: vec!(), obj_info: vec!(), elements: vec!() }
+  }
+  pub fn element(&self, name: &str) -> Option<&Element> {
+    self.elements.iter().find(|e| e.name == name)
+  }
+  pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+    Self::read(BufReader::new(std::fs::File::open(path)?))
+  }
+  // reads a PLY file in any of its formats, errors in the header or ascii data are numbered by
+  // line
+  pub fn read<R: BufRead>(mut r: R) -> Result<Self> {
+    let mut line_num = 0;
+    match read_line(&mut r, &mut line_num)? {
+      Some(l) if l.trim() == "ply" => (),
+      _ => return Err(Error::parse(1, ErrorKind::Malformed("missing ply header".to_string()))),
+    };
+    let mut format = None;
+    let mut t = Self::new(Format::Ascii);
+    loop {
+      let line = read_line(&mut r, &mut line_num)?
+        .ok_or_else(|| Error::parse(line_num, ErrorKind::UnexpectedEof))?;
+      let done = t.parse_header_line(&line, &mut format)
+        .map_err(|kind| Error::parse(line_num, kind))?;
+      if done { break };
+    }
+    t.format = format.ok_or_else(|| {
+      Error::parse(line_num, ErrorKind::Malformed("missing format".to_string()))
+    })?;
+    for e in t.elements.iter_mut() {
+      for _ in 0..e.count {
+        if t.format == Format::Ascii {
+          let line = loop {
+            match read_line(&mut r, &mut line_num)? {
+              None => return Err(Error::parse(line_num, ErrorKind::UnexpectedEof)),
+              Some(l) if l.trim().is_empty() => continue,
+              Some(l) => break l,
+            }
+          };
+          e.parse_row(&line).map_err(|kind| Error::parse(line_num, kind))?
+        } else {
+          e.read_row(&mut r, t.format)?
+        };
+      }
+    }
+    Ok(t)
+  }
+  // parses a line of the header, returning true at the end of the header
+  fn parse_header_line(
+    &mut self,
+    line: &str,
+    format: &mut Option<Format>,
+  ) -> ParseResult<bool> {
+    let tokens: Vec<_> = line.split_whitespace().collect();
+    let parse_type = |s: &str| Type::from_name(s)
+      .ok_or_else(|| ErrorKind::Unsupported(format!("property type {:?}", s)));
+    let rest = || line.trim().split_once(char::is_whitespace).map_or("", |(_, r)| r.trim());
+    match tokens.as_slice() {
+      [] => (),
+      ["comment", ..] => self.comments.push(rest().to_string()),
+      ["obj_info", ..] => self.obj_info.push(rest().to_string()),
+      ["format", f, version] => {
+        if !version.starts_with('1') {
+          return Err(ErrorKind::Unsupported(format!("version {}", version)))
+        }
+        *format = Some(match *f {
+          "ascii" => Format::Ascii,
+          "binary_little_endian" => Format::BinaryLittleEndian,
+          "binary_big_endian" => Format::BinaryBigEndian,
+          f => return Err(ErrorKind::Unsupported(format!("format {:?}", f))),
+        });
+      },
+      ["format", ..] =>
+        return Err(ErrorKind::ValueCount{ min: 2, max: 2, found: tokens.len() - 1 }),
+      ["element", name, count] => {
+        let count = count.parse().map_err(|_| ErrorKind::InvalidNumber(count.to_string()))?;
+        self.elements.push(Element{ count, ..Element::new(name) });
+      },
+      ["element", ..] =>
+        return Err(ErrorKind::ValueCount{ min: 2, max: 2, found: tokens.len() - 1 }),
+      ["property", ..] => {
+        let kind = match &tokens[1..] {
+          ["list", count, item, _] =>
+            PropertyKind::List{ count: parse_type(count)?, item: parse_type(item)? },
+          [t, _] => PropertyKind::Scalar(parse_type(t)?),
+          _ => return Err(ErrorKind::Malformed(format!("invalid property {:?}", line.trim()))),
+        };
+        let e = self.elements.last_mut()
+          .ok_or_else(|| ErrorKind::Malformed("property before any element".to_string()))?;
+        e.add_property(tokens.last().unwrap(), kind);
+      },
+      ["end_header"] => return Ok(true),
+      [k, ..] => return Err(ErrorKind::UnknownKeyword(k.to_string())),
+    };
+    Ok(false)
+  }
+  pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+    let mut w = BufWriter::new(std::fs::File::create(path)?);
+    self.write(&mut w)?;
+    Ok(w.flush()?)
+  }
+  pub fn write<W: Write>(&self, mut w: W) -> Result<()> {
+    let format = match self.format {
+      Format::Ascii => "ascii",
+      Format::BinaryLittleEndian => "binary_little_endian",
+      Format::BinaryBigEndian => "binary_big_endian",
+    };
+    writeln!(w, "ply\nformat {} 1.0", format)?;
+    for c in &self.comments { writeln!(w, "comment {}", c)? }
+    for o in &self.obj_info { writeln!(w, "obj_info {}", o)? }
+    for e in &self.elements {
+      e.check()?;
+      writeln!(w, "element {} {}", e.name, e.count)?;
+      for p in &e.properties {
+        match p.kind {
+          PropertyKind::Scalar(t) => writeln!(w, "property {} {}", t.name(), p.name)?,
+          PropertyKind::List{ count, item } =>
+            writeln!(w, "property list {} {} {}", count.name(), item.name(), p.name)?,
+        };
+      }
+    }
+    writeln!(w, "end_header")?;
+    for e in &self.elements {
+      for row in 0..e.count { e.write_row(&mut w, row, self.format)? }
+    }
+    Ok(())
+  }
+  // returns the x, y and z properties of each vertex
+  pub fn points<S: Scalar>(&self) -> ParseResult<Vec<Point<3, S>>> {
+    let vertex = self.element("vertex")
+      .ok_or_else(|| ErrorKind::MissingProperty("vertex".to_string()))?;
+    let [x, y, z] = columns(vertex, ["x", "y", "z"])
+      .ok_or_else(|| ErrorKind::MissingProperty("x, y and z".to_string()))?;
+    Ok((0..x.len()).map(|i| Point::from([x[i], y[i], z[i]].map(S::from_f64))).collect())
+  }
+  // creates a vertex element from pts, which are stored as floats if they can be exactly
+  pub fn from_points<S: Scalar>(pts: &[Point<3, S>], format: Format) -> Self {
+    let mut t = Self::new(format);
+    t.elements.push(vertex_element(pts, &["x", "y", "z"]));
+    t
+  }
+  // converts to a mesh of the vertex and face elements, using per vertex normals nx, ny and nz,
+  // and texture coordinates from u and v or s and t if they are present
+  pub fn to_mesh(&self) -> ParseResult<Mesh> {
+    let mut m = Mesh::new();
+    m.pts = self.points()?;
+    let vertex = self.element("vertex").unwrap();
+    if let Some([x, y, z]) = columns(vertex, ["nx", "ny", "nz"]) {
+      m.normals = (0..x.len())
+        .map(|i| Point::from([x[i] as f32, y[i] as f32, z[i] as f32]))
+        .collect();
+    }
+    let uv = columns(vertex, ["u", "v"])
+      .or_else(|| columns(vertex, ["s", "t"]))
+      .or_else(|| columns(vertex, ["texture_u", "texture_v"]));
+    if let Some([u, v]) = uv {
+      m.textures = (0..u.len()).map(|i| Point::from([u[i] as f32, v[i] as f32, 0.])).collect();
+    }
+    let face = match self.element("face") {
+      None => return Ok(m),
+      Some(face) => face,
+    };
+    let indices = face.property_index("vertex_indices")
+      .or_else(|| face.property_index("vertex_index"))
+      .ok_or_else(|| ErrorKind::MissingProperty("vertex_indices".to_string()))?;
+    let indices = &face.values[indices];
+    let (has_t, has_n) = (!m.textures.is_empty(), !m.normals.is_empty());
+    m.polygons = (0..face.count).map(|row| indices.get(row).iter().map(|&i| {
+      if i < 0. || i >= m.pts.len() as f64 { return Err(ErrorKind::IndexOutOfRange(i as i64)) };
+      let i = i as usize;
+      Ok((i, Some(i).filter(|_| has_t), Some(i).filter(|_| has_n)))
+    }).collect()).collect::<ParseResult<_>>()?;
+    Ok(m)
+  }
+  // converts a mesh into vertex and face elements. PLY stores normals and texture coordinates per
+  // vertex, so they are written only if there is one for each point, and the indices of them
+  // used by polygons are dropped.
+  pub fn from_mesh(m: &Mesh, format: Format) -> Self {
+    let mut t = Self::from_points(&m.pts, format);
+    let vertex = &mut t.elements[0];
+    let mut add = |pts: &[Point<3>], names: &[&str]| {
+      let e = vertex_element(pts, names);
+      vertex.properties.extend(e.properties);
+      vertex.values.extend(e.values);
+    };
+    if m.normals.len() == m.pts.len() { add(&m.normals, &["nx", "ny", "nz"]) };
+    if m.textures.len() == m.pts.len() { add(&m.textures, &["u", "v"]) };
+    let max_len = m.polygons.iter().map(|p| p.len()).max().unwrap_or(0);
+    let count = if max_len <= u8::MAX as usize { Type::UChar } else { Type::Int };
+    let mut face = Element{ count: m.polygons.len(), ..Element::new("face") };
+    face.add_property("vertex_indices", PropertyKind::List{ count, item: Type::Int });
+    for p in &m.polygons { face.values[0].push_list(p.iter().map(|&(v, _, _)| v as f64)) }
+    t.elements.push(face);
+    t
+  }
+}
+
+impl Mesh {
+  pub fn from_ply_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+    Self::read_ply(BufReader::new(std::fs::File::open(path)?))
+  }
+  pub fn read_ply<R: BufRead>(r: R) -> Result<Self> {
+    Ply::read(r)?.to_mesh().map_err(Error::Invalid)
+  }
+  pub fn to_ply_file<P: AsRef<Path>>(&self, path: P, format: Format) -> Result<()> {
+    Ply::from_mesh(self, format).to_file(path)
+  }
+  pub fn write_ply<W: Write>(&self, w: W, format: Format) -> Result<()> {
+    Ply::from_mesh(self, format).write(w)
+  }
+}
+
+pub fn read_points<S: Scalar, R: BufRead>(r: R) -> Result<Vec<Point<3, S>>> {
+  Ply::read(r)?.points().map_err(Error::Invalid)
+}
+
+pub fn write_points<S: Scalar, W: Write>(w: W, pts: &[Point<3, S>], format: Format) -> Result<()> {
+  Ply::from_points(pts, format).write(w)
+}
+
+// returns the named scalar properties of e, if all of them are present
+fn columns<'a, const K: usize>(e: &'a Element, names: [&str; K]) -> Option<[&'a [f64]; K]> {
+  let cols = names.iter().map(|n| e.column(n)).collect::<Option<Vec<_>>>()?;
+  cols.try_into().ok()
+}
+
+// creates a vertex element whose properties are the first coordinates of each point
+fn vertex_element<S: Scalar>(pts: &[Point<3, S>], names: &[&str]) -> Element {
+  let exact = pts.iter()
+    .flat_map(|p| p.iter())
+    .all(|v| exact_f32(v.to_f64()));
+  let t = if exact { Type::Float } else { Type::Double };
+  let mut e = Element{ count: pts.len(), ..Element::new("vertex") };
+  for (k, n) in names.iter().enumerate() {
+    e.properties.push(Property{ name: n.to_string(), kind: PropertyKind::Scalar(t) });
+    e.values.push(Values::Scalar(pts.iter().map(|p| p[k].to_f64()).collect()));
+  }
+  e
+}
+
+// reads a line, without its line ending, from r
+fn read_line<R: BufRead>(r: &mut R, line_num: &mut usize) -> Result<Option<String>> {
+  let mut line = String::new();
+  if r.read_line(&mut line)? == 0 { return Ok(None) };
+  *line_num += 1;
+  let end = line.trim_end_matches(&['\r', '\n'][..]).len();
+  line.truncate(end);
+  Ok(Some(line))
+}
+
+#[cfg(test)]
+mod ply_test {
+  use super::{Ply, Format, Type, PropertyKind};
+  use crate::{
+    mesh::Mesh,
+    point::Point,
+    error::{Error, ErrorKind},
+    test_util::expect_parse_error,
+  };
+  const SQUARE: &str = "ply
+format ascii 1.0
+comment a unit square
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property float nx
+property float ny
+property float nz
+element face 2
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0 1
+1 0 0 0 0 0 1
+1 1 0.5 0 0 0 1
+
+0 1 0 7 0 0 1
+3 0 1 2
+3 0 2 3
+";
+  #[test]
+  fn read_ascii() {
+    let ply = Ply::read(SQUARE.as_bytes()).unwrap();
+    assert_eq!(ply.format, Format::Ascii);
+    assert_eq!(ply.comments, vec!("a unit square"));
+    let vertex = ply.element("vertex").unwrap();
+    assert_eq!(vertex.column("red").unwrap(), vec!(255., 0., 0., 7.));
+    assert_eq!(ply.elements[1].properties[0].kind,
+      PropertyKind::List{ count: Type::UChar, item: Type::Int });
+    let pts = ply.points::<f64>().unwrap();
+    assert_eq!(pts[2], Point::from((1., 1., 0.5)));
+    let m = ply.to_mesh().unwrap();
+    assert_eq!(m.normals.len(), 4);
+    assert_eq!(m.polygons[1], vec!((0, None, Some(0)), (2, None, Some(2)), (3, None, Some(3))));
+  }
+  #[test]
+  fn round_trip() {
+    let ply = Ply::read(SQUARE.as_bytes()).unwrap();
+    for &format in &[Format::Ascii, Format::BinaryLittleEndian, Format::BinaryBigEndian] {
+      let mut buf = vec!();
+      Ply{ format, ..ply.clone() }.write(&mut buf).unwrap();
+      assert_eq!(Ply::read(buf.as_slice()).unwrap(), Ply{ format, ..ply.clone() });
+    }
+    let m = Mesh::read_ply(SQUARE.as_bytes()).unwrap();
+    let mut buf = vec!();
+    m.write_ply(&mut buf, Format::BinaryLittleEndian).unwrap();
+    assert_eq!(Mesh::read_ply(buf.as_slice()).unwrap(), m);
+    let pts = vec!(Point::<3, f64>::from((0.1, 2., 3.)), Point::from((1e-300, 0., -1.)));
+    let mut buf = vec!();
+    super::write_points(&mut buf, &pts, Format::Ascii).unwrap();
+    assert!(String::from_utf8_lossy(&buf).contains("property double x"));
+    assert_eq!(super::read_points::<f64, _>(buf.as_slice()).unwrap(), pts);
+  }
+  #[test]
+  fn ply_errors() {
+    let err = |s: &str| expect_parse_error(Ply::read(s.as_bytes()));
+    let header = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nend_header\n";
+    assert_eq!(err("obj\n").0, 1);
+    assert_eq!(err("ply\nformat ascii 1.0\nelement vertex 1\nproperty float32 x\n"),
+      (4, ErrorKind::UnexpectedEof));
+    assert_eq!(err("ply\nformat ascii 1.0\nproperty float x\n").0, 3);
+    assert_eq!(err("ply\nformat ascii 1.0\nelement v 1\nproperty half x\n"),
+      (4, ErrorKind::Unsupported("property type \"half\"".to_string())));
+    assert_eq!(err(&format!("{}abc\n", header)), (6, ErrorKind::InvalidNumber("abc".to_string())));
+    assert_eq!(err(&format!("{}1 2\n", header)).0, 6);
+    let truncated =
+      "ply\nformat binary_little_endian 1.0\nelement v 1\nproperty int x\nend_header\n\0";
+    assert!(matches!(Ply::read(truncated.as_bytes()), Err(Error::Io(_))));
+    let bad_index = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\n\
+      property float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\n\
+      end_header\n0 0 0\n3 0 0 1\n";
+    assert!(matches!(Mesh::read_ply(bad_index.as_bytes()),
+      Err(Error::Invalid(ErrorKind::IndexOutOfRange(1)))));
+  }
+}
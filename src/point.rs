@@ -3,117 +3,135 @@ use std::{
   ops::{Index, IndexMut},
   convert::{From},
 };
+use crate::scalar::Scalar;
 
-// Temporary representation of 3d point, might do more dimenstions later, idk
-#[derive(Clone, Copy, Debug, PartialEq, Default)]
-pub struct Point(f32, f32, f32);
+// Point in N dimensional space, with coordinates of type S
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point<const N: usize, S = f32>([S; N]);
 
-pub fn l2norm(a: &Point, b: &Point) -> f32 {
-  assert_eq!(a.len(), b.len());
-  (0..a.len())
-    .map(|d| (a[d] - b[d]).powi(2))
-    .sum::<f32>()
-    .powf(0.5)
+pub fn l2norm<S: Scalar, const N: usize>(a: &Point<N, S>, b: &Point<N, S>) -> S {
+  S::from_f64(l2norm_sq(a, b).sqrt())
 }
 
-pub fn l1norm(a: &Point, b: &Point) -> f32 {
-  assert_eq!(a.len(), b.len());
-  (0..a.len())
+// squared l2norm, accumulated in f64 so that integer coordinates far apart do not overflow.
+// It is exact for integer coordinates while it stays below 2^53.
+pub fn l2norm_sq<S: Scalar, const N: usize>(a: &Point<N, S>, b: &Point<N, S>) -> f64 {
+  (0..N)
+    .map(|d| a[d].to_f64() - b[d].to_f64())
+    .map(|v| v * v)
+    .sum::<f64>()
+}
+
+pub fn l1norm<S: Scalar, const N: usize>(a: &Point<N, S>, b: &Point<N, S>) -> S {
+  (0..N)
     .map(|d| (a[d] - b[d]).abs())
-    .sum::<f32>()
+    .sum::<S>()
 }
 
-impl Index<usize> for Point {
-  type Output = f32;
-  fn index(&self, i: usize) -> &f32 {
-    match i {
-      0 => &self.0,
-      1 => &self.1,
-      2 => &self.2,
-      _ => panic!("Index out of bounds"),
-    }
+impl<S: Scalar, const N: usize> Default for Point<N, S> {
+  fn default() -> Self { Point([S::ZERO; N]) }
+}
+
+impl<S, const N: usize> Index<usize> for Point<N, S> {
+  type Output = S;
+  fn index(&self, i: usize) -> &S {
+    self.0.get(i).expect("Index out of bounds")
   }
 }
 
-impl IndexMut<usize> for Point {
+impl<S, const N: usize> IndexMut<usize> for Point<N, S> {
   fn index_mut<'a>(&'a mut self, i: usize) -> &'a mut Self::Output {
-    match i {
-      0 => &mut self.0,
-      1 => &mut self.1,
-      2 => &mut self.2,
-      _ => panic!("Index out of bounds"),
-    }
+    self.0.get_mut(i).expect("Index out of bounds")
   }
 }
 
-impl std::iter::FromIterator<f32> for Point {
-  fn from_iter<I: IntoIterator<Item=f32>>(iter: I) -> Self {
-    let mut i = iter.into_iter();
-    Point(i.next().unwrap(), i.next().unwrap(), i.next().unwrap())
+impl<S: Scalar, const N: usize> std::iter::FromIterator<S> for Point<N, S> {
+  fn from_iter<I: IntoIterator<Item=S>>(iter: I) -> Self {
+    let mut out = Point::default();
+    out.extend(iter);
+    out
   }
 }
 
-impl Point {
-  pub fn len(&self) -> usize { 3 }
-  pub fn iter(&self) -> Iter { Iter(0, &self) }
-  pub fn dist(&self, o: &Self) -> f32 { l2norm(self, o) }
-  pub fn get(&self, d: usize) -> Option<f32> {
-    match d {
-      0 | 1 | 2 => Some(self[d]),
-      _ => None,
-    }
+impl<S: Scalar, const N: usize> Point<N, S> {
+  pub fn len(&self) -> usize { N }
+  pub fn is_empty(&self) -> bool { N == 0 }
+  pub fn iter(&self) -> Iter<'_, N, S> { Iter(0, self) }
+  pub fn dist(&self, o: &Self) -> S { l2norm(self, o) }
+  pub fn dist_sq(&self, o: &Self) -> f64 { l2norm_sq(self, o) }
+  pub fn get(&self, d: usize) -> Option<S> { self.0.get(d).copied() }
+  pub fn coords(&self) -> &[S; N] { &self.0 }
+  // converts each coordinate to another scalar type, going through f64
+  pub fn cast<T: Scalar>(&self) -> Point<N, T> {
+    self.iter().map(|v| T::from_f64(v.to_f64())).collect()
   }
 }
 
-pub fn variances(p: &[Point]) -> Vec<f32> {
+pub fn variances<S: Scalar, const N: usize>(p: &[Point<N, S>]) -> Vec<f64> {
   if p.is_empty() { return vec!() };
-  (0..p[0].len())
-    .map(|d| crate::util::variance(p.iter().map(|p| p[d])))
+  (0..N)
+    .map(|d| crate::util::variance(p.iter().map(|p| p[d].to_f64())))
     .collect()
 }
 
+// covariance matrix of the coordinates of p, whose diagonal is the same as variances(p)
+pub fn covariance<S: Scalar, const N: usize>(p: &[Point<N, S>]) -> [[f64; N]; N] {
+  let mut c = [[0.; N]; N];
+  if p.is_empty() { return c };
+  let mean: Vec<_> = (0..N)
+    .map(|d| crate::util::average(p.iter().map(|p| p[d].to_f64())))
+    .collect();
+  let dev = |p: &Point<N, S>, d: usize| p[d].to_f64() - mean[d];
+  for (i, row) in c.iter_mut().enumerate() {
+    for (j, v) in row.iter_mut().enumerate() {
+      *v = crate::util::average(p.iter().map(|p| dev(p, i) * dev(p, j)));
+    }
+  }
+  c
+}
+
 #[derive(Clone, Copy, Debug)]
-pub struct Iter<'a>(i32, &'a Point);
+pub struct Iter<'a, const N: usize, S>(usize, &'a Point<N, S>);
 
-impl Iterator for Iter<'_> {
-  type Item = f32;
+impl<S: Scalar, const N: usize> Iterator for Iter<'_, N, S> {
+  type Item = S;
   fn next(&mut self) -> Option<Self::Item> {
-    let out = match self.0 {
-      0 => Some(self.1[0]),
-      1 => Some(self.1[1]),
-      2 => Some(self.1[2]),
-      _ => None
-    };
+    let out = self.1.get(self.0);
     self.0 += 1;
     out
   }
 }
 
-impl Extend<f32> for Point {
-  fn extend<I: IntoIterator<Item=f32>>(&mut self, iter: I) {
+impl<S: Scalar, const N: usize> Extend<S> for Point<N, S> {
+  fn extend<I: IntoIterator<Item=S>>(&mut self, iter: I) {
     let mut i = iter.into_iter();
-    self.0 = i.next().unwrap();
-    self.1 = i.next().unwrap();
-    self.2 = i.next().unwrap();
+    self.0.iter_mut().for_each(|v| *v = i.next().unwrap());
   }
 }
 
-impl From<&Vec<f32>> for Point {
-  fn from(v: &Vec<f32>) -> Self {
-    Point(
-      v.get(0).copied().unwrap_or(0.),
-      v.get(1).copied().unwrap_or(0.),
-      v.get(2).copied().unwrap_or(0.),
-    )
+// Missing trailing dimensions are zero, and extra values are ignored
+impl<S: Scalar, const N: usize> From<&Vec<S>> for Point<N, S> {
+  fn from(v: &Vec<S>) -> Self {
+    let mut out = Point::default();
+    v.iter().zip(out.0.iter_mut()).for_each(|(&v, o)| *o = v);
+    out
   }
 }
 
-impl From<f32> for Point {
-  fn from(v: f32) -> Self { Point(v,v,v) }
+impl<S: Scalar, const N: usize> From<[S; N]> for Point<N, S> {
+  fn from(v: [S; N]) -> Self { Point(v) }
+}
+
+impl<S: Scalar, const N: usize> From<S> for Point<N, S> {
+  fn from(v: S) -> Self { Point([v; N]) }
 }
 
-impl From<(f32, f32, f32)> for Point {
-  fn from(v: (f32, f32,f32)) -> Self { Point(v.0, v.1, v.2) }
+impl<S: Scalar> From<(S, S)> for Point<2, S> {
+  fn from(v: (S, S)) -> Self { Point([v.0, v.1]) }
+}
+
+impl<S: Scalar> From<(S, S, S)> for Point<3, S> {
+  fn from(v: (S, S, S)) -> Self { Point([v.0, v.1, v.2]) }
 }
 
 #[cfg(test)]
@@ -121,18 +139,46 @@ mod tests {
   use super::Point;
   #[test]
   fn test_point() {
-    let p: Point = Default::default();
-    assert_eq!(p.0, p.1);
-    assert_eq!(p.0, p.2);
-    assert_eq!(p.0, 0.);
+    let p: Point<3> = Default::default();
+    assert_eq!(p[0], p[1]);
+    assert_eq!(p[0], p[2]);
+    assert_eq!(p[0], 0.);
     assert!(p.iter().all(|v| v == 0.));
     assert_eq!(p.iter().count(), 3);
+    let p: Point<6> = (0..6).map(|i| i as f32).collect();
+    assert_eq!(p.iter().count(), 6);
+    assert_eq!(p.get(5), Some(5.));
+    assert_eq!(p.get(6), None);
+    assert_eq!(Point::<2>::from(&vec!(1., 2., 3.)), Point::from((1., 2.)));
+    assert_eq!(Point::<4>::from(&vec!(1., 2.)), Point::from([1., 2., 0., 0.]));
   }
 
   #[test]
   fn test_dist() {
-    let origin: Point = Default::default();
+    let origin: Point<3> = Default::default();
     let v = Point::from((3., 4., 0.));
     assert_eq!(origin.dist(&v), 5.);
+    assert_eq!(Point::default().dist(&Point::from((3., 4.))), 5.);
+    assert_eq!(Point::default().dist(&Point::from((3i32, 5))), 5);
+    assert_eq!(Point::default().dist_sq(&Point::from((3i64, 5))), 34.);
+    // squares which overflow the coordinate type
+    assert_eq!(Point::<2, i16>::default().dist(&Point::from((200, 0))), 200);
+    let a = Point::from((-2_000_000_000i32, 1_000_000_000));
+    assert_eq!(a.dist_sq(&Point::from((2_000_000_000, 1_000_000_000))), 1.6e19);
+    assert_eq!(Point::from((0i64, 3_000_000_000)).dist(&Point::from((4_000_000_000, 0))),
+      5_000_000_000);
+    let far = Point::from((4_000_000.0f64, 5_000_000.25, 1.));
+    assert_eq!(far.dist(&Point::from((4_000_000., 5_000_000., 1.))), 0.25);
+    assert_eq!(far.cast::<i32>(), Point::from((4_000_000, 5_000_000, 1)));
+  }
+
+  #[test]
+  fn test_covariance() {
+    let pts: Vec<Point<2>> = vec!((0., 0.), (1., 2.), (2., 4.), (3., 6.)).into_iter()
+      .map(Point::from)
+      .collect();
+    let c = super::covariance(&pts);
+    assert_eq!(c, [[1.25, 2.5], [2.5, 5.]]);
+    assert_eq!(super::variances(&pts), vec!(c[0][0], c[1][1]));
   }
 }
@@ -0,0 +1,452 @@
+use crate::{
+  point::{Point, covariance},
+  scalar::Scalar,
+  util::{average, symmetric_eigen, solve, Rng},
+};
+
+// A shape which can be fit to data by random sampling
+pub trait Model: Sized {
+  // what is fit, usually a point
+  type Item: Clone;
+  // fewest items which determine a model, which is the size of each random sample
+  fn sample_size() -> usize;
+  // fits exactly to a sample, or by least squares to more items. None if they are degenerate.
+  fn fit(items: &[Self::Item]) -> Option<Self>;
+  // distance of an item from the model
+  fn residual(&self, item: &Self::Item) -> f64;
+}
+
+fn coords<const N: usize, S: Scalar>(p: &Point<N, S>) -> [f64; N] { p.coords().map(S::to_f64) }
+
+fn dot<const N: usize>(a: &[f64; N], b: &[f64; N]) -> f64 { (0..N).map(|k| a[k] * b[k]).sum() }
+
+fn sub<const N: usize>(mut a: [f64; N], b: &[f64; N]) -> [f64; N] {
+  (0..N).for_each(|k| a[k] -= b[k]);
+  a
+}
+
+// removes the part of a along the unit vector dir
+fn reject<const N: usize>(mut a: [f64; N], dir: &[f64; N]) -> [f64; N] {
+  let t = dot(&a, dir);
+  (0..N).for_each(|k| a[k] -= t * dir[k]);
+  a
+}
+
+fn unit<const N: usize>(mut a: [f64; N]) -> Option<[f64; N]> {
+  let len = dot(&a, &a).sqrt();
+  if len.is_nan() || len <= 0. { return None };
+  (0..N).for_each(|k| a[k] /= len);
+  Some(a)
+}
+
+fn mean<const N: usize, S: Scalar>(pts: &[Point<N, S>]) -> [f64; N] {
+  let mut m = [0.; N];
+  (0..N).for_each(|k| m[k] = average(pts.iter().map(|p| p[k].to_f64())));
+  m
+}
+
+// Hyperplane of the points p where normal.p + d = 0, with a unit normal. In 3D the coefficients
+// of ax + by + cz + d = 0 are the normal followed by d.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane<const N: usize, S = f32> {
+  pub normal: Point<N, S>,
+  pub d: S,
+}
+
+impl<const N: usize, S: Scalar> Plane<N, S> {
+  // positive on the side the normal faces
+  pub fn signed_dist(&self, p: &Point<N, S>) -> S {
+    S::from_f64(dot(&coords(&self.normal), &coords(p)) + self.d.to_f64())
+  }
+  pub fn dist(&self, p: &Point<N, S>) -> S { self.signed_dist(p).abs() }
+}
+
+impl<const N: usize, S: Scalar> Model for Plane<N, S> {
+  type Item = Point<N, S>;
+  fn sample_size() -> usize { N }
+  // least squares fit, or None if the points do not determine a single plane
+  fn fit(pts: &[Point<N, S>]) -> Option<Self> {
+    if pts.len() < N { return None };
+    let eig = symmetric_eigen(covariance(pts));
+    let total: f64 = eig.iter().map(|(val, _)| val.max(0.)).sum();
+    // the normal could point anywhere in the span of the two smallest directions
+    if total <= 0. || (N > 1 && eig[1].0 <= total * 1e-12) { return None };
+    let normal = eig[0].1;
+    let d = -dot(&normal, &mean(pts));
+    Some(Plane{ normal: Point::from(normal.map(S::from_f64)), d: S::from_f64(d) })
+  }
+  fn residual(&self, p: &Point<N, S>) -> f64 { self.dist(p).to_f64() }
+}
+
+// Line through point along a unit direction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Line<const N: usize, S = f32> {
+  pub point: Point<N, S>,
+  pub dir: Point<N, S>,
+}
+
+impl<const N: usize, S: Scalar> Line<N, S> {
+  pub fn dist(&self, p: &Point<N, S>) -> S {
+    let off = reject(sub(coords(p), &coords(&self.point)), &coords(&self.dir));
+    S::from_f64(dot(&off, &off).sqrt())
+  }
+}
+
+impl<const N: usize, S: Scalar> Model for Line<N, S> {
+  type Item = Point<N, S>;
+  fn sample_size() -> usize { 2 }
+  // least squares fit through the centroid along the direction of greatest variance
+  fn fit(pts: &[Point<N, S>]) -> Option<Self> {
+    if pts.len() < 2 { return None };
+    let eig = symmetric_eigen(covariance(pts));
+    if eig[N-1].0 <= 0. { return None };
+    Some(Line{
+      point: Point::from(mean(pts).map(S::from_f64)),
+      dir: Point::from(eig[N-1].1.map(S::from_f64)),
+    })
+  }
+  fn residual(&self, p: &Point<N, S>) -> f64 { self.dist(p).to_f64() }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere<const N: usize, S = f32> {
+  pub center: Point<N, S>,
+  pub radius: S,
+}
+
+impl<const N: usize, S: Scalar> Sphere<N, S> {
+  // distance from the surface, whether inside or outside
+  pub fn dist(&self, p: &Point<N, S>) -> S {
+    let off = sub(coords(p), &coords(&self.center));
+    S::from_f64((dot(&off, &off).sqrt() - self.radius.to_f64()).abs())
+  }
+}
+
+impl<const N: usize, S: Scalar> Model for Sphere<N, S> {
+  type Item = Point<N, S>;
+  fn sample_size() -> usize { N + 1 }
+  // Algebraic least squares fit of |p|^2 + D.p + E = 0, relative to the centroid of pts.
+  // None if the points lie on a hyperplane or the fit has no real radius.
+  fn fit(pts: &[Point<N, S>]) -> Option<Self> {
+    if pts.len() < N + 1 { return None };
+    let m = mean(pts);
+    let mut ata = vec!(vec!(0.; N + 1); N + 1);
+    let mut atb = vec!(0.; N + 1);
+    for p in pts {
+      let q = sub(coords(p), &m);
+      let row: Vec<_> = q.iter().copied().chain(std::iter::once(1.)).collect();
+      for i in 0..=N {
+        (0..=N).for_each(|j| ata[i][j] += row[i] * row[j]);
+        atb[i] -= row[i] * dot(&q, &q);
+      }
+    }
+    let x = solve(ata, atb)?;
+    let mut center = [0.; N];
+    (0..N).for_each(|k| center[k] = -x[k] / 2.);
+    let r_sq = dot(&center, &center) - x[N];
+    if r_sq.is_nan() || r_sq <= 0. { return None };
+    (0..N).for_each(|k| center[k] += m[k]);
+    Some(Sphere{ center: Point::from(center.map(S::from_f64)), radius: S::from_f64(r_sq.sqrt()) })
+  }
+  fn residual(&self, p: &Point<N, S>) -> f64 { self.dist(p).to_f64() }
+}
+
+// Infinite cylinder around the line through point along a unit axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cylinder<S = f32> {
+  pub point: Point<3, S>,
+  pub axis: Point<3, S>,
+  pub radius: S,
+}
+
+impl<S: Scalar> Cylinder<S> {
+  // distance from the surface, whether inside or outside
+  pub fn dist(&self, p: &Point<3, S>) -> S {
+    let off = reject(sub(coords(p), &coords(&self.point)), &coords(&self.axis));
+    S::from_f64((dot(&off, &off).sqrt() - self.radius.to_f64()).abs())
+  }
+}
+
+// Fit to points paired with their normals, such as from estimate_normals, which only need to be
+// accurate up to sign. The residual is only the distance of the point from the surface.
+impl<S: Scalar> Model for Cylinder<S> {
+  type Item = (Point<3, S>, Point<3, S>);
+  fn sample_size() -> usize { 2 }
+  // The axis is the direction most perpendicular to all normals, and it passes through the
+  // point nearest to every line along a normal, in the least squares sense. None if the
+  // normals are parallel.
+  fn fit(items: &[(Point<3, S>, Point<3, S>)]) -> Option<Self> {
+    if items.len() < 2 { return None };
+    let normals = items.iter().map(|(_, n)| unit(coords(n))).collect::<Option<Vec<_>>>()?;
+    let mut scatter = [[0.; 3]; 3];
+    for n in &normals {
+      (0..3).for_each(|i| (0..3).for_each(|j| scatter[i][j] += n[i] * n[j]));
+    }
+    let eig = symmetric_eigen(scatter);
+    if eig[1].0 <= eig[2].0 * 1e-9 { return None };
+    let axis = eig[0].1;
+    // the normal lines are projected along the axis, so the center lies on the plane through
+    // the origin perpendicular to it
+    let mut lhs = vec!(vec!(0.; 3); 3);
+    let mut rhs = vec!(0.; 3);
+    for ((p, _), n) in items.iter().zip(&normals) {
+      let q = reject(coords(p), &axis);
+      let n = match unit(reject(*n, &axis)) {
+        None => continue,
+        Some(n) => n,
+      };
+      for i in 0..3 {
+        for j in 0..3 {
+          let proj = if i == j { 1. } else { 0. } - n[i] * n[j];
+          lhs[i][j] += proj;
+          rhs[i] += proj * q[j];
+        }
+      }
+    }
+    let center = solve(lhs, rhs)?;
+    let center = [center[0], center[1], center[2]];
+    let radius = average(items.iter().map(|(p, _)| {
+      let off = reject(sub(coords(p), &center), &axis);
+      dot(&off, &off).sqrt()
+    }));
+    if radius.is_nan() || radius <= 0. { return None };
+    Some(Cylinder{
+      point: Point::from(center.map(S::from_f64)),
+      axis: Point::from(axis.map(S::from_f64)),
+      radius: S::from_f64(radius),
+    })
+  }
+  fn residual(&self, (p, _): &(Point<3, S>, Point<3, S>)) -> f64 { self.dist(p).to_f64() }
+}
+
+// How the model of each sample is judged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+  // the number of inliers
+  Inliers,
+  // MSAC, the sum of squared residuals with each capped at the threshold, which prefers closer
+  // fits among those with similar numbers of inliers
+  Msac,
+}
+
+// Settings for fitting models to data by random sampling, where an item is an inlier of a model
+// if its residual is within threshold. The same seed always gives the same result.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ransac<S = f32> {
+  pub threshold: S,
+  // number of random samples tried for each model
+  pub iterations: usize,
+  pub seed: u64,
+  pub score: Score,
+}
+
+impl<S: Scalar> Ransac<S> {
+  pub fn new(threshold: S, iterations: usize, seed: u64) -> Self {
+    Ransac{ threshold, iterations, seed, score: Score::Inliers }
+  }
+  pub fn with_score(mut self, score: Score) -> Self {
+    self.score = score;
+    self
+  }
+  // Finds the model with the best score over random samples, refit by least squares to its
+  // inliers. Returns it with the indices of the items which are inliers of the refit model, or
+  // None if every sample was degenerate.
+  pub fn fit<M: Model>(&self, items: &[M::Item]) -> Option<(M, Vec<usize>)> {
+    let all: Vec<_> = (0..items.len()).collect();
+    self.fit_among(items, &all, &mut Rng::new(self.seed))
+  }
+  // Repeatedly finds a model as fit does and removes its inliers before finding the next,
+  // until max_models are found or the next has fewer than min_inliers. Inliers are indices
+  // into items, and each item is an inlier of at most one model.
+  pub fn fit_many<M: Model>(
+    &self,
+    items: &[M::Item],
+    max_models: usize,
+    min_inliers: usize,
+  ) -> Vec<(M, Vec<usize>)> {
+    let mut rng = Rng::new(self.seed);
+    let mut remaining: Vec<_> = (0..items.len()).collect();
+    let mut out = vec!();
+    while out.len() < max_models {
+      let (model, inliers) = match self.fit_among(items, &remaining, &mut rng) {
+        Some(found) if found.1.len() >= min_inliers.max(1) => found,
+        _ => break,
+      };
+      let mut is_inlier = vec!(false; items.len());
+      inliers.iter().for_each(|&i| is_inlier[i] = true);
+      remaining.retain(|&i| !is_inlier[i]);
+      out.push((model, inliers));
+    }
+    out
+  }
+  // fit for planes through pts
+  pub fn plane<const N: usize>(&self, pts: &[Point<N, S>]) -> Option<(Plane<N, S>, Vec<usize>)> {
+    self.fit(pts)
+  }
+  // fit_many for planes through pts
+  pub fn planes<const N: usize>(
+    &self,
+    pts: &[Point<N, S>],
+    max_planes: usize,
+    min_inliers: usize,
+  ) -> Vec<(Plane<N, S>, Vec<usize>)> {
+    self.fit_many(pts, max_planes, min_inliers)
+  }
+  // fits a model to only the items at indices among
+  fn fit_among<M: Model>(
+    &self,
+    items: &[M::Item],
+    among: &[usize],
+    rng: &mut Rng,
+  ) -> Option<(M, Vec<usize>)> {
+    let k = M::sample_size();
+    if among.len() < k { return None };
+    let t = self.threshold.to_f64();
+    let inliers = |m: &M| -> Vec<usize> {
+      among.iter().copied().filter(|&i| m.residual(&items[i]) <= t).collect()
+    };
+    // lower is better
+    let cost = |m: &M| -> f64 {
+      among.iter().map(|&i| m.residual(&items[i])).map(|r| match self.score {
+        Score::Inliers => if r <= t { -1. } else { 0. },
+        Score::Msac => (r * r).min(t * t),
+      }).sum()
+    };
+    let mut best: Option<(f64, M)> = None;
+    for _ in 0..self.iterations {
+      let sample: Vec<_> = rng.distinct(among.len(), k).iter()
+        .map(|&i| items[among[i]].clone())
+        .collect();
+      let model = match M::fit(&sample) {
+        None => continue,
+        Some(model) => model,
+      };
+      let c = cost(&model);
+      if best.as_ref().is_none_or(|(b, _)| c < *b) { best = Some((c, model)) };
+    }
+    let (_, model) = best?;
+    // keeps the sampled model if its inliers are degenerate
+    let found: Vec<_> = inliers(&model).iter().map(|&i| items[i].clone()).collect();
+    let model = M::fit(&found).unwrap_or(model);
+    let found = inliers(&model);
+    Some((model, found))
+  }
+}
+
+#[cfg(test)]
+mod ransac_test {
+  use super::{Model, Plane, Line, Sphere, Cylinder, Ransac, Score};
+  use crate::{
+    point::Point,
+    util::Rng,
+  };
+  // points on z = 0 and x = 5 with scattered outliers
+  fn scene() -> Vec<Point<3, f64>> {
+    let mut r = Rng::new(7);
+    let mut noise = |scale: f64| (r.below(1000) as f64 / 1000. - 0.5) * scale;
+    let mut pts = vec!();
+    for i in 0..200 {
+      pts.push(Point::from(((i % 20) as f64 * 0.2, (i / 20) as f64 * 0.4, noise(0.01))));
+    }
+    for i in 0..120 {
+      let (y, z) = ((i % 12) as f64 * 0.3, (i / 12) as f64 * 0.3 + 1.);
+      pts.push(Point::from((5. + noise(0.01), y, z)));
+    }
+    for _ in 0..40 {
+      pts.push(Point::from((noise(10.) - 1., noise(10.), noise(10.) + 8.)));
+    }
+    pts
+  }
+  #[test]
+  fn fit_plane() {
+    let plane = Plane::fit(&[Point::<3, f64>::from((0., 0., 1.)), Point::from((1., 0., 1.)),
+      Point::from((0., 1., 1.)), Point::from((1., 1., 1.))]).unwrap();
+    assert!((plane.normal[2].abs() - 1.).abs() < 1e-9);
+    assert!((plane.dist(&Point::from((3., 4., 3.))) - 2.).abs() < 1e-6);
+    assert_eq!(plane.signed_dist(&Point::from((0., 0., 0.))), plane.d);
+    // collinear points do not determine a plane
+    let line: Vec<Point<3>> = (0..5).map(|i| Point::from(i as f32)).collect();
+    assert_eq!(Plane::fit(&line), None);
+    assert_eq!(Plane::fit(&line[..2]), None);
+  }
+  #[test]
+  fn segment() {
+    let pts = scene();
+    let ransac = Ransac::new(0.05, 200, 1);
+    let (ground, inliers) = ransac.plane(&pts).unwrap();
+    assert_eq!(inliers, (0..200).collect::<Vec<_>>());
+    assert!(ground.normal[2].abs() > 0.999 && ground.d.abs() < 0.01);
+    assert_eq!(ransac.plane(&pts), ransac.plane(&pts));
+    let planes = ransac.planes(&pts, 5, 50);
+    assert_eq!(planes.len(), 2);
+    assert_eq!(planes[1].1, (200..320).collect::<Vec<_>>());
+    let wall = planes[1].0;
+    assert!(wall.normal[0].abs() > 0.999);
+    assert!(wall.dist(&Point::from((5., 0., 0.))) < 0.01);
+    assert_eq!(ransac.planes(&pts, 1, 0).len(), 1);
+    assert_eq!(ransac.plane::<3>(&[]), None);
+  }
+  // uniform in -scale/2..scale/2
+  fn noise(r: &mut Rng, scale: f64) -> f64 { (r.below(1000) as f64 / 1000. - 0.5) * scale }
+  #[test]
+  fn line() {
+    let mut r = Rng::new(3);
+    let mut pts: Vec<Point<3, f64>> = (0..50)
+      .map(|i| Point::from((i as f64 * 0.1, 1. + i as f64 * 0.2, 2. + noise(&mut r, 0.01))))
+      .collect();
+    pts.extend((0..20).map(|_| Point::from((noise(&mut r, 8.), noise(&mut r, 8.), 5.))));
+    let ransac = Ransac::new(0.02, 50, 9).with_score(Score::Msac);
+    let (line, inliers): (Line<3, f64>, _) = ransac.fit(&pts).unwrap();
+    assert_eq!(inliers, (0..50).collect::<Vec<_>>());
+    let dir = [1. / 5f64.sqrt(), 2. / 5f64.sqrt(), 0.];
+    assert!((0..3).map(|k| line.dir[k] * dir[k]).sum::<f64>().abs() > 0.9999);
+    assert!(line.dist(&Point::from((0., 1., 2.))) < 0.01);
+    assert_eq!(Line::fit(&[Point::<2>::from((1., 1.)); 2]), None);
+  }
+  #[test]
+  fn sphere() {
+    let mut r = Rng::new(5);
+    let mut pts: Vec<Point<3, f64>> = (0..150).map(|i| {
+      let (a, b) = (i as f64 * 0.7, i as f64 * 0.02);
+      let radius = 2. + noise(&mut r, 0.01);
+      Point::from((1. + radius * a.cos() * b.sin(), 2. + radius * a.sin() * b.sin(),
+        3. + radius * b.cos()))
+    }).collect();
+    pts.extend((0..50).map(|_| Point::from((1. + noise(&mut r, 2.), 2. + noise(&mut r, 2.), 3.))));
+    let (sphere, inliers): (Sphere<3, f64>, _) = Ransac::new(0.02, 100, 2).fit(&pts).unwrap();
+    assert_eq!(inliers, (0..150).collect::<Vec<_>>());
+    assert!(sphere.center.dist(&Point::from((1., 2., 3.))) < 0.01);
+    assert!((sphere.radius - 2.).abs() < 0.01);
+    let circle = Sphere::<2>::fit(&[Point::from((0., 1.)), Point::from((1., 0.)),
+      Point::from((-1., 0.))]).unwrap();
+    assert!(circle.center.dist(&Point::default()) < 1e-6 && (circle.radius - 1.).abs() < 1e-6);
+    let square = [(0., 0., 1.), (1., 0., 1.), (0., 1., 1.), (1., 1., 1.)].map(Point::from);
+    assert_eq!(Sphere::<3>::fit(&square), None);
+  }
+  #[test]
+  fn cylinder() {
+    let mut r = Rng::new(11);
+    // around the line x = 1, y = -1 with radius 0.5
+    let mut items: Vec<(Point<3, f64>, Point<3, f64>)> = (0..120).map(|i| {
+      let a = i as f64 * 0.37;
+      let n = Point::from((a.cos(), a.sin(), noise(&mut r, 0.02)));
+      // normals are only known up to sign
+      let n = if i % 2 == 0 { n } else { Point::from((-n[0], -n[1], -n[2])) };
+      let p = Point::from((1. + 0.5 * a.cos(), -1. + 0.5 * a.sin(), i as f64 * 0.05));
+      (p, n)
+    }).collect();
+    items.extend((0..40).map(|_| {
+      let p = Point::from((noise(&mut r, 6.), noise(&mut r, 6.), noise(&mut r, 6.)));
+      (p, Point::from((noise(&mut r, 1.), noise(&mut r, 1.), noise(&mut r, 1.))))
+    }));
+    let ransac = Ransac::new(0.01, 100, 4).with_score(Score::Msac);
+    let (cyl, inliers): (Cylinder<f64>, _) = ransac.fit(&items).unwrap();
+    assert!(inliers.iter().take(120).copied().eq(0..120));
+    assert!(cyl.axis[2].abs() > 0.999);
+    assert!((cyl.radius - 0.5).abs() < 0.005);
+    assert!(cyl.dist(&Point::from((1.5, -1., 10.))) < 0.005);
+    let parallel = [(Point::from((0., 0., 0.)), Point::from((1., 0., 0.))),
+      (Point::from((1., 1., 0.)), Point::from((-1., 0., 0.)))];
+    assert_eq!(Cylinder::<f32>::fit(&parallel), None);
+  }
+}
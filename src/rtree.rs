@@ -1,55 +1,604 @@
-use crate::bounding_box::BoundingBox;
+use std::mem;
+use crate::{
+  bounded::Bounded,
+  bounding_box::BoundingBox,
+  point::Point,
+  scalar::Scalar,
+};
 
-pub struct RTree {
+// R-tree over items with bounding boxes, every page is at the same depth
+#[derive(Debug)]
+pub struct RTree<T, const N: usize, S = f32> {
   size: usize,
+  root: RNode<T, N, S>,
+  // number of branches between the root and any page
+  height: usize,
+  max_per_lvl: usize,
+  min_per_lvl: usize,
+  strategy: Strategy,
+}
 
-  pages: Vec<RPage>,
-  page_depth: usize,
-  dist: fn(&BoundingBox, &BoundingBox) -> f32,
+const MAX_PER_LVL: usize = 9;
 
-  branches: Vec<RBranch>,
+// How to pick where entries go when inserting and splitting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+  // Guttman's quadratic split
+  Quadratic,
+  // Beckmann et al.'s R*-tree, which minimizes overlap and margin when choosing subtrees and
+  // splitting, and reinserts some entries of a full node before splitting it
+  RStar,
 }
 
-const MAX_PER_LVL: usize = 9;
-const MAX_ENTRIES: usize = 100_000;
-const ROOT: usize = 0;
+// children of a node, each with its bounds
+type Entries<X, const N: usize, S> = Vec<(BoundingBox<N, S>, X)>;
+
+#[derive(Debug)]
+enum RNode<T, const N: usize, S> {
+  // leaf which holds items
+  Page(Entries<T, N, S>),
+  Branch(Entries<RNode<T, N, S>, N, S>),
+}
+
+// Something to be inserted at some height of the tree
+enum Entry<T, const N: usize, S> {
+  Item(T),
+  Node(RNode<T, N, S>),
+}
+
+// entry waiting to be inserted at a height, along with its bounds
+type Orphan<T, const N: usize, S> = (usize, BoundingBox<N, S>, Entry<T, N, S>);
+
+// State kept over the course of inserting a single entry
+struct Insertion<T, const N: usize, S> {
+  max: usize,
+  min: usize,
+  strategy: Strategy,
+  tree_height: usize,
+  // whether an overflow at each height has already been handled by reinsertion
+  reinserted: Vec<bool>,
+  // entries which were removed for reinsertion, with the height they belong at
+  pending: Vec<Orphan<T, N, S>>,
+}
 
-pub struct RPage(BoundingBox, Vec<(BoundingBox, T)>);
-pub struct RBranch {
-  // depth of this node
-  // will need to check if children are 1 + depth
-  depth: usize,
-  bb: BoundingBox,
-  children: Vec<usize>,
+impl<T: Bounded<N, S>, const N: usize, S: Scalar> Default for RTree<T, N, S> {
+  fn default() -> Self { Self::new() }
 }
 
-impl RTree {
-  pub fn new() -> Self {
+impl<T: Bounded<N, S>, const N: usize, S: Scalar> RTree<T, N, S> {
+  pub fn new() -> Self { Self::with_max(MAX_PER_LVL) }
+  pub fn rstar() -> Self { Self::with_strategy(MAX_PER_LVL, Strategy::RStar) }
+  // creates an rtree with at most max_per_lvl children for each node
+  pub fn with_max(max_per_lvl: usize) -> Self {
+    Self::with_strategy(max_per_lvl, Strategy::Quadratic)
+  }
+  pub fn with_strategy(max_per_lvl: usize, strategy: Strategy) -> Self {
+    assert!(max_per_lvl >= 4);
     RTree{
       size: 0,
-      pages: vec!(),
-      page_depth: 1,
-      branches: vec!(RBranch::new(0)),
-    }
-  }
-  pub fn add(&mut self, item: (BoundingBox, T) {
-    let mut curr = branches[ROOT];
-    while curr.depth < self.page_depth + 1 {
-      let next = curr.children.iter()
-        .map(|c_index| self.branches[c_index])
-        .map(|c| (c, self.dist(&c.bb, &item.0)))
-        .min_by_key(|(_, d)| d);
-      // TODO handle next being some(i.e. selected)
-      // or none aka no children
-      unimplemented!();
+      root: RNode::Page(vec!()),
+      height: 0,
+      max_per_lvl,
+      // Guttman suggests 40% of the max
+      min_per_lvl: (2 * max_per_lvl).div_ceil(5),
+      strategy,
+    }
+  }
+  // packs all of v at once using Sort-Tile-Recursive, which leaves nodes nearly full
+  pub fn from(v: Vec<T>) -> Self { Self::from_with(v, MAX_PER_LVL, Strategy::Quadratic) }
+  pub fn from_with(v: Vec<T>, max_per_lvl: usize, strategy: Strategy) -> Self {
+    let mut t = Self::with_strategy(max_per_lvl, strategy);
+    if v.is_empty() { return t };
+    t.size = v.len();
+    let items = v.into_iter().map(|item| (item.bounds(), item)).collect();
+    let (max, min) = (t.max_per_lvl, t.min_per_lvl);
+    let mut lvl: Vec<_> = sort_tile_recursive(items, max, min).into_iter()
+      .map(RNode::Page)
+      .collect();
+    while lvl.len() > 1 {
+      let children = lvl.into_iter().map(|n| (n.bounds(), n)).collect();
+      lvl = sort_tile_recursive(children, max, min).into_iter().map(RNode::Branch).collect();
+      t.height += 1;
+    }
+    t.root = lvl.pop().unwrap();
+    t
+  }
+  pub fn add(&mut self, item: T) {
+    self.size += 1;
+    self.insert(item.bounds(), Entry::Item(item), 0);
+  }
+  // inserts e into a node at the given height, where pages are at height 0
+  fn insert(&mut self, bb: BoundingBox<N, S>, e: Entry<T, N, S>, height: usize) {
+    let mut ins = Insertion{
+      max: self.max_per_lvl,
+      min: self.min_per_lvl,
+      strategy: self.strategy,
+      tree_height: self.height,
+      reinserted: vec!(false; self.height + 1),
+      pending: vec!((height, bb, e)),
     };
-    unimplemented!();
+    while let Some((height, bb, e)) = ins.pending.pop() {
+      assert!(height <= self.height);
+      if let Some(sibling) = self.root.insert(bb, e, self.height, height, &mut ins) {
+        let old = mem::replace(&mut self.root, RNode::Branch(vec!()));
+        self.root = RNode::Branch(vec!((old.bounds(), old), (sibling.bounds(), sibling)));
+        self.height += 1;
+        ins.tree_height += 1;
+        ins.reinserted.push(false);
+      }
+    }
+  }
+  // removes an item equal to the given one, returning it
+  pub fn remove(&mut self, item: &T) -> Option<T> where T: PartialEq {
+    let mut orphans = vec!();
+    let removed = self.root.remove(&item.bounds(), item, self.height, self.min_per_lvl,
+      &mut orphans)?;
+    self.size -= 1;
+    loop {
+      match &mut self.root {
+        RNode::Branch(children) if children.len() == 1 => {
+          self.root = children.pop().unwrap().1;
+          self.height -= 1;
+        },
+        RNode::Branch(children) if children.is_empty() => {
+          self.root = RNode::Page(vec!());
+          self.height = 0;
+        },
+        _ => break,
+      }
+    }
+    orphans.into_iter().for_each(|(height, bb, e)| self.insert(bb, e, height));
+    Some(removed)
+  }
+  pub fn size(&self) -> usize { self.size }
+  pub fn is_empty(&self) -> bool { self.size == 0 }
+  pub fn depth(&self) -> usize { self.height + 1 }
+  pub fn bounds(&self) -> Option<BoundingBox<N, S>> { self.root.try_bounds() }
+  // returns all items which overlap with b
+  pub fn window(&self, b: &BoundingBox<N, S>) -> Vec<&T> {
+    let mut buf = vec!();
+    self.root.search(&|n| n.overlaps(b), &|i| i.overlaps(b), &mut buf);
+    buf
+  }
+  // returns all items which are entirely inside of b
+  pub fn contained_in(&self, b: &BoundingBox<N, S>) -> Vec<&T> {
+    let mut buf = vec!();
+    self.root.search(&|n| n.intersects(b), &|i| b.surrounds(i), &mut buf);
+    buf
+  }
+  // returns all items whose bounds contain p
+  pub fn containing(&self, p: &Point<N, S>) -> Vec<&T> {
+    let mut buf = vec!();
+    self.root.search(&|n| n.contains(p), &|i| i.contains(p), &mut buf);
+    buf
+  }
+  pub fn iter(&self) -> impl Iterator<Item=&T> {
+    let mut buf = vec!();
+    self.root.search(&|_| true, &|_| true, &mut buf);
+    buf.into_iter()
+  }
+  #[cfg(test)]
+  fn is_valid(&self) -> bool {
+    assert_eq!(self.size, self.root.is_valid(self.height, self.min_per_lvl, self.max_per_lvl));
+    true
+  }
+}
+
+impl<T, const N: usize, S: Scalar> RNode<T, N, S> {
+  fn len(&self) -> usize {
+    match self {
+      RNode::Page(items) => items.len(),
+      RNode::Branch(children) => children.len(),
+    }
+  }
+  fn try_bounds(&self) -> Option<BoundingBox<N, S>> {
+    match self {
+      RNode::Page(items) => union_all(items),
+      RNode::Branch(children) => union_all(children),
+    }
+  }
+  fn bounds(&self) -> BoundingBox<N, S> { self.try_bounds().expect("Empty node has no bounds") }
+  // inserts e into the subtree at the given height, where this node is at self_height.
+  // Returns a new sibling for this node if it had to be split.
+  fn insert(&mut self, bb: BoundingBox<N, S>, e: Entry<T, N, S>, self_height: usize,
+    height: usize, ins: &mut Insertion<T, N, S>) -> Option<Self> {
+    match (&mut *self, e) {
+      (RNode::Page(items), Entry::Item(item)) => {
+        assert_eq!(height, 0);
+        items.push((bb, item));
+      },
+      (RNode::Branch(children), Entry::Node(n)) if self_height == height => {
+        children.push((bb, n));
+      },
+      (RNode::Branch(children), e) => {
+        let overlap = ins.strategy == Strategy::RStar && self_height == 1;
+        let i = choose_subtree(children, &bb, overlap);
+        let sibling = children[i].1.insert(bb, e, self_height - 1, height, ins);
+        children[i].0 = children[i].1.bounds();
+        children.extend(sibling.map(|s| (s.bounds(), s)));
+      },
+      (RNode::Page(_), Entry::Node(_)) => panic!("Cannot insert node into a page"),
+    };
+    if self.len() <= ins.max { return None };
+    let is_root = self_height == ins.tree_height;
+    if ins.strategy == Strategy::RStar && !is_root && !ins.reinserted[self_height] {
+      ins.reinserted[self_height] = true;
+      self.take_for_reinsert(self_height, ins);
+      return None
+    }
+    Some(match self {
+      RNode::Page(items) => RNode::Page(split(items, ins)),
+      RNode::Branch(children) => RNode::Branch(split(children, ins)),
+    })
+  }
+  // removes the entries farthest from the center of this node and marks them for reinsertion
+  fn take_for_reinsert(&mut self, self_height: usize, ins: &mut Insertion<T, N, S>) {
+    // reinserting 30% is what is suggested by the R*-tree paper
+    let p = (3 * ins.max).div_ceil(10);
+    let center = self.bounds().center();
+    // pending is popped from the back, so the closest of those taken are reinserted first
+    match self {
+      RNode::Page(items) => ins.pending.extend(take_farthest(items, &center, p).into_iter()
+        .map(|(bb, item)| (0, bb, Entry::Item(item)))),
+      RNode::Branch(children) => ins.pending.extend(take_farthest(children, &center, p)
+        .into_iter()
+        .map(|(bb, n)| (self_height, bb, Entry::Node(n)))),
+    }
+  }
+  // removes item from the subtree, pushing the contents of nodes which are left underfull into
+  // orphans along with the height they should be reinserted at.
+  fn remove(&mut self, bb: &BoundingBox<N, S>, item: &T, self_height: usize, min: usize,
+    orphans: &mut Vec<Orphan<T, N, S>>) -> Option<T>
+    where T: PartialEq {
+    let children = match self {
+      RNode::Page(items) => {
+        let i = items.iter().position(|(b, t)| b == bb && t == item)?;
+        return Some(items.swap_remove(i).1)
+      },
+      RNode::Branch(children) => children,
+    };
+    for i in 0..children.len() {
+      if !children[i].0.surrounds(bb) { continue };
+      let removed = match children[i].1.remove(bb, item, self_height - 1, min, orphans) {
+        None => continue,
+        Some(removed) => removed,
+      };
+      if children[i].1.len() >= min { children[i].0 = children[i].1.bounds() }
+      else {
+        match children.swap_remove(i).1 {
+          RNode::Page(items) => orphans.extend(items.into_iter()
+            .map(|(bb, item)| (0, bb, Entry::Item(item)))),
+          RNode::Branch(grandchildren) => orphans.extend(grandchildren.into_iter()
+            .map(|(bb, n)| (self_height - 1, bb, Entry::Node(n)))),
+        }
+      }
+      return Some(removed)
+    }
+    None
+  }
+  // pushes all items which pass keep, only descending into nodes which pass descend
+  fn search<'a>(&'a self, descend: &impl Fn(&BoundingBox<N, S>) -> bool,
+    keep: &impl Fn(&BoundingBox<N, S>) -> bool, buf: &mut Vec<&'a T>) {
+    match self {
+      RNode::Page(items) => buf.extend(items.iter().filter(|(bb, _)| keep(bb)).map(|(_, t)| t)),
+      RNode::Branch(children) => children.iter()
+        .filter(|(bb, _)| descend(bb))
+        .for_each(|(_, c)| c.search(descend, keep, buf)),
+    }
+  }
+  #[cfg(test)]
+  fn count_pages(&self) -> usize {
+    match self {
+      RNode::Page(_) => 1,
+      RNode::Branch(children) => children.iter().map(|(_, c)| c.count_pages()).sum(),
+    }
+  }
+  // checks the bounds and sizes of each node, returning the number of items
+  #[cfg(test)]
+  fn is_valid(&self, self_height: usize, min: usize, max: usize) -> usize {
+    assert!(self.len() <= max);
+    match self {
+      RNode::Page(items) => {
+        assert_eq!(self_height, 0);
+        items.len()
+      },
+      RNode::Branch(children) => {
+        assert!(self_height > 0);
+        children.iter().map(|(bb, c)| {
+          assert!(c.len() >= min, "Underfull node with {} entries", c.len());
+          assert_eq!(bb, &c.bounds());
+          c.is_valid(self_height - 1, min, max)
+        }).sum()
+      },
+    }
+  }
+}
+
+fn union_all<X, const N: usize, S: Scalar>(v: &[(BoundingBox<N, S>, X)])
+  -> Option<BoundingBox<N, S>> {
+  let (first, rest) = v.split_first()?;
+  Some(rest.iter().fold(first.0, |acc, (bb, _)| acc.union(bb)))
+}
+
+fn enlargement<S: Scalar, const N: usize>(bb: &BoundingBox<N, S>, o: &BoundingBox<N, S>) -> f64 {
+  bb.union(o).volume() - bb.volume()
+}
+
+fn overlap<S: Scalar, const N: usize>(a: &BoundingBox<N, S>, b: &BoundingBox<N, S>) -> f64 {
+  if a.intersects(b) { a.intersection(b).volume() } else { 0. }
+}
+
+// picks the child needing the least enlargement to include bb, breaking ties by smallest volume.
+// If by_overlap, first picks the child whose overlap with its siblings would grow the least.
+fn choose_subtree<X, const N: usize, S: Scalar>(children: &[(BoundingBox<N, S>, X)],
+  bb: &BoundingBox<N, S>, by_overlap: bool) -> usize {
+  let overlap_growth = |i: usize| {
+    if !by_overlap { return 0. };
+    let (c, grown) = (&children[i].0, children[i].0.union(bb));
+    children.iter().enumerate()
+      .filter(|&(j, _)| i != j)
+      .map(|(_, (o, _))| overlap(&grown, o) - overlap(c, o))
+      .sum::<f64>()
+  };
+  children.iter()
+    .enumerate()
+    .map(|(i, (c, _))| (overlap_growth(i), enlargement(c, bb), c.volume()))
+    .enumerate()
+    .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+    .unwrap().0
+}
+
+// splits v into chunks of size, evening out the last two if the last would be smaller than min
+fn chunk<X>(v: Vec<X>, size: usize, min: usize) -> Vec<Vec<X>> {
+  let mut rest = v.into_iter().peekable();
+  let mut out = vec!();
+  while rest.peek().is_some() { out.push(rest.by_ref().take(size).collect::<Vec<_>>()) }
+  if out.len() > 1 && out.last().unwrap().len() < min {
+    let mut last = out.pop().unwrap();
+    let mut prev = out.pop().unwrap();
+    prev.append(&mut last);
+    let half = prev.split_off(prev.len()/2);
+    out.push(prev);
+    out.push(half);
   }
+  out
 }
 
-impl RBranch {
-  pub fn new(depth: usize) -> Self {
-    let children = vec!();
-    RBranch{depth, children}
+// Groups entries into nodes of at most max by sorting on the first axis, cutting it into slabs,
+// and then recursing on the next axis within each slab
+fn sort_tile_recursive<X, const N: usize, S: Scalar>(v: Entries<X, N, S>,
+  max: usize, min: usize) -> Vec<Entries<X, N, S>> {
+  fn tile<X, const N: usize, S: Scalar>(mut v: Entries<X, N, S>, d: usize,
+    max: usize, min: usize, out: &mut Vec<Entries<X, N, S>>) {
+    let nodes = v.len().div_ceil(max);
+    if nodes <= 1 { return out.push(v) };
+    v.sort_by(|(a, _), (b, _)| a.center()[d].partial_cmp(&b.center()[d])
+      .unwrap_or(std::cmp::Ordering::Equal));
+    if d + 1 == N { return out.extend(chunk(v, max, min)) };
+    // each remaining axis gets about the same number of slabs
+    let remaining = (N - d) as u32;
+    let mut slabs = (nodes as f64).powf(1./remaining as f64).ceil() as usize;
+    while slabs > 1 && (slabs - 1).pow(remaining) >= nodes { slabs -= 1 }
+    let per_slab = max * nodes.div_ceil(slabs);
+    chunk(v, per_slab, min).into_iter().for_each(|slab| tile(slab, d + 1, max, min, out));
+  }
+  let mut out = vec!();
+  tile(v, 0, max, min, &mut out);
+  out
+}
+
+// removes the p entries whose centers are farthest from center, ordered farthest first
+fn take_farthest<X, const N: usize, S: Scalar>(v: &mut Entries<X, N, S>,
+  center: &Point<N, S>, p: usize) -> Entries<X, N, S> {
+  v.sort_by(|(a, _), (b, _)| a.center().dist_sq(center)
+    .partial_cmp(&b.center().dist_sq(center))
+    .unwrap_or(std::cmp::Ordering::Equal));
+  let mut taken = v.split_off(v.len() - p);
+  taken.reverse();
+  taken
+}
+
+// splits an overflowing node, leaving one group in entries and returning the other
+fn split<X, T, const N: usize, S: Scalar>(entries: &mut Entries<X, N, S>,
+  ins: &Insertion<T, N, S>) -> Entries<X, N, S> {
+  match ins.strategy {
+    Strategy::Quadratic => quadratic_split(entries, ins.min),
+    Strategy::RStar => rstar_split(entries, ins.min),
+  }
+}
+
+// sorts entries by either their lower or upper bound on the axis d
+fn sort_on<X, const N: usize, S: Scalar>(entries: &mut [(BoundingBox<N, S>, X)], d: usize,
+  upper: bool) {
+  let key = |bb: &BoundingBox<N, S>| if upper { bb.max_on(d) } else { bb.min_on(d) };
+  entries.sort_by(|(a, _), (b, _)| key(a).partial_cmp(&key(b))
+    .unwrap_or(std::cmp::Ordering::Equal));
+}
+
+// For each distribution of sorted entries into a first group of k and the rest, returns k and
+// the bounds of both groups
+fn distributions<X, const N: usize, S: Scalar>(entries: &[(BoundingBox<N, S>, X)], min: usize)
+  -> Vec<(usize, BoundingBox<N, S>, BoundingBox<N, S>)> {
+  let n = entries.len();
+  let mut prefix = vec!(entries[0].0);
+  (1..n).for_each(|i| prefix.push(prefix[i-1].union(&entries[i].0)));
+  let mut suffix = vec!(entries[n-1].0);
+  (1..n).for_each(|i| suffix.push(suffix[i-1].union(&entries[n-1-i].0)));
+  (min..=n-min).map(|k| (k, prefix[k-1], suffix[n-k-1])).collect()
+}
+
+// R*-tree split, which picks the axis with the least total margin over all distributions, then
+// the distribution on that axis with the least overlap
+fn rstar_split<X, const N: usize, S: Scalar>(entries: &mut Entries<X, N, S>,
+  min: usize) -> Entries<X, N, S> {
+  use std::cmp::Ordering;
+  assert!(entries.len() >= 2 * min);
+  let axis = (0..N).map(|d| {
+    let margin = [false, true].iter().map(|&upper| {
+      sort_on(entries, d, upper);
+      distributions(entries, min).iter().map(|(_, a, b)| a.margin() + b.margin()).sum::<f64>()
+    }).sum::<f64>();
+    (d, margin)
+  }).min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal)).unwrap().0;
+  let (upper, k, _) = [false, true].iter().flat_map(|&upper| {
+    sort_on(entries, axis, upper);
+    distributions(entries, min).into_iter()
+      .map(move |(k, a, b)| (upper, k, (overlap(&a, &b), a.volume() + b.volume())))
+  }).collect::<Vec<_>>().into_iter()
+    .min_by(|(_, _, a), (_, _, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal)).unwrap();
+  sort_on(entries, axis, upper);
+  entries.split_off(k)
+}
+
+// Guttman's quadratic split, leaving one group in entries and returning the other
+fn quadratic_split<X, const N: usize, S: Scalar>(entries: &mut Entries<X, N, S>,
+  min: usize) -> Entries<X, N, S> {
+  let n = entries.len();
+  assert!(n >= 2);
+  // seeds are the pair which would waste the most space if put together
+  let (mut s1, mut s2, mut worst) = (0, 1, f64::NEG_INFINITY);
+  (0..n).for_each(|i| (i+1..n).for_each(|j| {
+    let (a, b) = (&entries[i].0, &entries[j].0);
+    let waste = a.union(b).volume() - a.volume() - b.volume();
+    if waste > worst { s1 = i; s2 = j; worst = waste; }
+  }));
+  let mut rest = mem::take(entries);
+  // s2 > s1 so removing it first leaves s1 in place
+  let mut b = vec!(rest.swap_remove(s2));
+  let mut a = vec!(rest.swap_remove(s1));
+  let (mut a_bb, mut b_bb) = (a[0].0, b[0].0);
+  while !rest.is_empty() {
+    if a.len() + rest.len() <= min { a.append(&mut rest); break };
+    if b.len() + rest.len() <= min { b.append(&mut rest); break };
+    // next is the one with the greatest preference for a group
+    let i = rest.iter()
+      .map(|(bb, _)| (enlargement(&a_bb, bb) - enlargement(&b_bb, bb)).abs())
+      .enumerate()
+      .max_by(|(_, x), (_, y)| x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal))
+      .unwrap().0;
+    let next = rest.swap_remove(i);
+    let (da, db) = (enlargement(&a_bb, &next.0), enlargement(&b_bb, &next.0));
+    let to_a = if da != db { da < db }
+      else if a_bb.volume() != b_bb.volume() { a_bb.volume() < b_bb.volume() }
+      else { a.len() <= b.len() };
+    if to_a { a_bb = a_bb.union(&next.0); a.push(next) }
+    else { b_bb = b_bb.union(&next.0); b.push(next) }
+  }
+  *entries = a;
+  b
+}
+
+#[cfg(test)]
+mod rtree_test {
+  use super::{RTree, Strategy};
+  use crate::{
+    bounding_box::BoundingBox,
+    point::Point,
+    test_util::BadRand,
+  };
+  fn rand_box(r: &mut BadRand, cap: i64) -> BoundingBox<2> {
+    let ll = Point::from((r.i64(cap) as f32, r.i64(cap) as f32));
+    let size = Point::from((r.i64(cap/10) as f32, r.i64(cap/10) as f32));
+    BoundingBox::new(ll, Point::from((ll[0] + size[0], ll[1] + size[1])))
+  }
+  #[test]
+  fn basic_test() {
+    let mut t = RTree::new();
+    assert!(t.is_empty());
+    assert_eq!(t.bounds(), None);
+    let items: Vec<Point<2>> = vec!((0., 0.), (1., 1.), (2., 2.), (5., 5.)).into_iter()
+      .map(Point::from)
+      .collect();
+    items.iter().for_each(|p| t.add(*p));
+    assert_eq!(t.size(), 4);
+    assert!(t.is_valid());
+    assert_eq!(t.bounds(), Some(BoundingBox::new(Point::from(0.), Point::from(5.))));
+    let window = BoundingBox::new(Point::from(0.5), Point::from(3.));
+    assert_eq!(t.window(&window).len(), 2);
+    assert_eq!(t.contained_in(&window).len(), 2);
+    assert_eq!(t.containing(&Point::from(5.)), vec!(&items[3]));
+    assert_eq!(t.remove(&items[1]), Some(items[1]));
+    assert_eq!(t.remove(&items[1]), None);
+    assert_eq!(t.window(&window), vec!(&items[2]));
+    assert!(t.is_valid());
+  }
+  fn check_all(mut t: RTree<BoundingBox<2>, 2>) {
+    let mut r = BadRand::new();
+    let cap = 1000;
+    let boxes: Vec<_> = (0..500).map(|_| rand_box(&mut r, cap)).collect();
+    boxes.iter().for_each(|b| t.add(*b));
+    assert!(t.is_valid());
+    assert_eq!(t.size(), boxes.len());
+    assert_eq!(t.iter().count(), boxes.len());
+    assert!(t.depth() > 2);
+    (0..50).for_each(|_| {
+      let q = rand_box(&mut r, cap);
+      let found = t.window(&q);
+      assert!(found.iter().all(|b| b.overlaps(&q)));
+      assert_eq!(found.len(), boxes.iter().filter(|b| b.overlaps(&q)).count());
+      assert_eq!(t.contained_in(&q).len(), boxes.iter().filter(|b| q.surrounds(b)).count());
+      let p = q.center();
+      assert_eq!(t.containing(&p).len(), boxes.iter().filter(|b| b.contains(&p)).count());
+    });
+    boxes.iter().enumerate().for_each(|(i, b)| {
+      assert_eq!(t.remove(b).as_ref(), Some(b));
+      assert_eq!(t.size(), boxes.len() - i - 1);
+      assert!(t.is_valid());
+    });
+    assert!(t.is_empty());
+    assert_eq!(t.depth(), 1);
+  }
+  #[test]
+  fn gen_test() { check_all(RTree::with_max(4)) }
+  #[test]
+  fn bulk_test() {
+    let mut r = BadRand::new();
+    let cap = 1000;
+    assert!(RTree::<BoundingBox<2>, 2>::from(vec!()).is_empty());
+    let boxes: Vec<_> = (0..500).map(|_| rand_box(&mut r, cap)).collect();
+    let t = RTree::from_with(boxes.clone(), 4, Strategy::Quadratic);
+    assert!(t.is_valid());
+    assert_eq!(t.size(), boxes.len());
+    // 125 full pages would be ideal
+    assert!(t.root.count_pages() <= 130);
+    let mut incremental = RTree::with_max(4);
+    boxes.iter().for_each(|b| incremental.add(*b));
+    assert!(t.depth() <= incremental.depth());
+    (0..50).for_each(|_| {
+      let q = rand_box(&mut r, cap);
+      assert_eq!(t.window(&q).len(), boxes.iter().filter(|b| b.overlaps(&q)).count());
+    });
+    let points: Vec<Point<3>> = (0..1000)
+      .map(|_| Point::from((r.i64(cap) as f32, r.i64(cap) as f32, r.i64(cap) as f32)))
+      .collect();
+    let mut t = RTree::from(points.clone());
+    assert!(t.is_valid());
+    assert_eq!(t.bounds(), RTree::from(points.clone()).bounds());
+    points.iter().take(500).for_each(|p| assert_eq!(t.remove(p), Some(*p)));
+    points.iter().take(100).for_each(|p| t.add(*p));
+    assert!(t.is_valid());
+    assert_eq!(t.size(), 600);
+  }
+  #[test]
+  fn int_test() {
+    // boxes spanning all of i32 have sides too long for i32
+    let mut boxes: Vec<_> = (0..40)
+      .map(|i| BoundingBox::new(Point::from((i, i % 7)), Point::from((i + 3, i % 7 + 2))))
+      .collect();
+    boxes.push(BoundingBox::<2, i32>::inf());
+    boxes.push(BoundingBox::new(Point::from((i32::MIN, 0)), Point::from((i32::MAX, 1))));
+    for strategy in &[Strategy::Quadratic, Strategy::RStar] {
+      let mut t = RTree::with_strategy(4, *strategy);
+      boxes.iter().for_each(|b| t.add(*b));
+      assert!(t.is_valid());
+      assert_eq!(t.containing(&Point::from((-100, 0))).len(), 2);
+      assert_eq!(t.remove(&boxes[40]), Some(boxes[40]));
+      assert!(t.is_valid());
+    }
+  }
+  #[test]
+  fn rstar_test() {
+    check_all(RTree::with_strategy(4, Strategy::RStar));
+    check_all(RTree::rstar());
   }
 }
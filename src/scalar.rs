@@ -0,0 +1,96 @@
+use std::{
+  fmt::Debug,
+  iter::{Sum, Product},
+  ops::{Add, Sub, Mul, Div, Neg},
+};
+
+// Numeric type which can be used as the coordinate of a point.
+// Integer types stand in for floats where they can, so INFINITY is their max value and sqrt
+// rounds down.
+pub trait Scalar: Copy + Debug + Default + PartialEq + PartialOrd
+  + Add<Output=Self> + Sub<Output=Self> + Mul<Output=Self> + Div<Output=Self> + Neg<Output=Self>
+  + Sum + Product {
+  const ZERO: Self;
+  const ONE: Self;
+  const INFINITY: Self;
+  const NEG_INFINITY: Self;
+  fn abs(self) -> Self;
+  fn sqrt(self) -> Self;
+  fn is_finite(self) -> bool;
+  fn is_nan(self) -> bool;
+  fn from_f64(v: f64) -> Self;
+  fn to_f64(self) -> f64;
+  fn min(self, o: Self) -> Self { if o < self { o } else { self } }
+  fn max(self, o: Self) -> Self { if o > self { o } else { self } }
+}
+
+macro_rules! float_scalar {
+  ($t: ident) => {
+    impl Scalar for $t {
+      const ZERO: Self = 0.;
+      const ONE: Self = 1.;
+      const INFINITY: Self = <$t>::INFINITY;
+      const NEG_INFINITY: Self = <$t>::NEG_INFINITY;
+      fn abs(self) -> Self { $t::abs(self) }
+      fn sqrt(self) -> Self { $t::sqrt(self) }
+      fn is_finite(self) -> bool { $t::is_finite(self) }
+      fn is_nan(self) -> bool { $t::is_nan(self) }
+      fn from_f64(v: f64) -> Self { v as $t }
+      fn to_f64(self) -> f64 { self as f64 }
+      fn min(self, o: Self) -> Self { $t::min(self, o) }
+      fn max(self, o: Self) -> Self { $t::max(self, o) }
+    }
+  };
+}
+
+macro_rules! int_scalar {
+  ($t: ident) => {
+    impl Scalar for $t {
+      const ZERO: Self = 0;
+      const ONE: Self = 1;
+      const INFINITY: Self = <$t>::MAX;
+      const NEG_INFINITY: Self = <$t>::MIN;
+      fn abs(self) -> Self { $t::abs(self) }
+      fn sqrt(self) -> Self {
+        if self <= 0 { return 0 };
+        // float sqrt is only approximate for large values so correct it after
+        let mut r = (self as f64).sqrt() as $t;
+        while r.checked_mul(r).map_or(true, |sq| sq > self) { r -= 1 }
+        while (r+1).checked_mul(r+1).map_or(false, |sq| sq <= self) { r += 1 }
+        r
+      }
+      fn is_finite(self) -> bool { self != Self::INFINITY && self != Self::NEG_INFINITY }
+      fn is_nan(self) -> bool { false }
+      fn from_f64(v: f64) -> Self { v as $t }
+      fn to_f64(self) -> f64 { self as f64 }
+    }
+  };
+}
+
+float_scalar!(f32);
+float_scalar!(f64);
+int_scalar!(i16);
+int_scalar!(i32);
+int_scalar!(i64);
+
+#[cfg(test)]
+mod scalar_test {
+  use super::Scalar;
+  #[test]
+  fn int_sqrt() {
+    (0..1000i64).for_each(|i| {
+      let r = Scalar::sqrt(i);
+      assert!(r*r <= i && (r+1)*(r+1) > i);
+    });
+    assert_eq!(Scalar::sqrt(i64::MAX), 3037000499);
+    assert_eq!(Scalar::sqrt(-4i32), 0);
+  }
+  #[test]
+  fn infinities() {
+    assert!(!Scalar::is_finite(<i32 as Scalar>::INFINITY));
+    assert!(Scalar::is_finite(0i32));
+    assert!(!Scalar::is_finite(<f64 as Scalar>::NEG_INFINITY));
+    assert_eq!(Scalar::min(3i16, -2), -2);
+    assert_eq!(Scalar::max(3f32, -2.), 3.);
+  }
+}
@@ -0,0 +1,245 @@
+use crate::{
+  point::Point,
+  mesh::Mesh,
+  error::{Error, ErrorKind, Result, ParseResult},
+};
+use std::{
+  collections::HashMap,
+  convert::TryInto,
+  io::{BufReader, BufRead, BufWriter, Write},
+  path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  Ascii,
+  Binary,
+}
+
+const HEADER_LEN: usize = 80;
+// normal and three corners as f32 followed by a u16 attribute
+const TRIANGLE_LEN: usize = 50;
+
+impl Mesh {
+  pub fn from_stl_file<P: AsRef<Path>>(path: P, weld: bool) -> Result<Self> {
+    Self::read_stl(BufReader::new(std::fs::File::open(path)?), weld)
+  }
+  // reads an ascii or binary STL file, which is detected from its contents. Each triangle keeps
+  // its facet normal. If weld is true, corners at the same position share an index into pts,
+  // otherwise every triangle has its own three points.
+  pub fn read_stl<R: BufRead>(mut r: R, weld: bool) -> Result<Self> {
+    let mut buf = vec!();
+    r.read_to_end(&mut buf)?;
+    let mut t = Self::new();
+    let mut welder = Welder{ weld, indices: HashMap::new() };
+    if is_binary(&buf) {
+      if buf.len() < HEADER_LEN + 4 { return Err(Error::Invalid(ErrorKind::UnexpectedEof)) };
+      let count = u32::from_le_bytes(buf[HEADER_LEN..HEADER_LEN + 4].try_into().unwrap());
+      let tris = &buf[HEADER_LEN + 4..];
+      if tris.len() < count as usize * TRIANGLE_LEN {
+        return Err(Error::Invalid(ErrorKind::UnexpectedEof))
+      }
+      for tri in tris.chunks_exact(TRIANGLE_LEN).take(count as usize) {
+        let f = |i: usize| f32::from_le_bytes(tri[4 * i..4 * i + 4].try_into().unwrap());
+        let p = |i: usize| Point::from((f(3 * i), f(3 * i + 1), f(3 * i + 2)));
+        t.add_facet(&mut welder, p(0), [p(1), p(2), p(3)]);
+      }
+    } else {
+      let text = std::str::from_utf8(&buf)
+        .map_err(|_| Error::Invalid(ErrorKind::Malformed("stl is not valid text".to_string())))?;
+      let mut facet = None;
+      for (i, line) in text.lines().enumerate() {
+        t.parse_stl_line(line, &mut facet, &mut welder).map_err(|kind| Error::parse(i + 1, kind))?;
+      }
+      if facet.is_some() { return Err(Error::Invalid(ErrorKind::UnexpectedEof)) };
+    }
+    Ok(t)
+  }
+  fn parse_stl_line(
+    &mut self,
+    line: &str,
+    facet: &mut Option<(Point<3>, Vec<Point<3>>)>,
+    welder: &mut Welder,
+  ) -> ParseResult<()> {
+    let tokens: Vec<_> = line.split_whitespace().collect();
+    let unexpected = |k: &str| ErrorKind::Malformed(format!("unexpected {:?}", k));
+    match (&tokens[..], facet.as_mut()) {
+      ([], _) => (),
+      (["solid", ..], None) | (["endsolid", ..], None) => (),
+      (["facet", "normal", args @ ..], None) => *facet = Some((parse_point(args)?, vec!())),
+      (["outer", "loop"], Some(_)) | (["endloop"], Some(_)) => (),
+      (["vertex", args @ ..], Some((_, corners))) => corners.push(parse_point(args)?),
+      (["endfacet"], Some(_)) => {
+        let (normal, corners) = facet.take().unwrap();
+        if corners.len() != 3 {
+          return Err(ErrorKind::ValueCount{ min: 3, max: 3, found: corners.len() })
+        }
+        self.add_facet(welder, normal, [corners[0], corners[1], corners[2]]);
+      },
+      ([k, ..], _) if ["solid", "endsolid", "facet", "outer", "endloop", "vertex", "endfacet"]
+        .contains(k) => return Err(unexpected(k)),
+      ([k, ..], _) => return Err(ErrorKind::UnknownKeyword(k.to_string())),
+    };
+    Ok(())
+  }
+  fn add_facet(&mut self, welder: &mut Welder, normal: Point<3>, corners: [Point<3>; 3]) {
+    let n = self.normals.len();
+    self.normals.push(normal);
+    let poly = corners.iter().map(|&c| (welder.index(&mut self.pts, c), None, Some(n))).collect();
+    self.polygons.push(poly);
+  }
+  pub fn to_stl_file<P: AsRef<Path>>(&self, path: P, format: Format) -> Result<()> {
+    let mut w = BufWriter::new(std::fs::File::create(path)?);
+    self.write_stl(&mut w, format)?;
+    Ok(w.flush()?)
+  }
+  // writes the polygons as triangles, splitting larger polygons into a fan around their first
+  // corner. Facet normals are computed from the triangles rather than taken from normals.
+  pub fn write_stl<W: Write>(&self, mut w: W, format: Format) -> Result<()> {
+    let tris = self.polygons.iter().flat_map(|p| {
+      (2..p.len()).map(move |i| [self.pts[p[0].0], self.pts[p[i - 1].0], self.pts[p[i].0]])
+    });
+    match format {
+      Format::Ascii => {
+        writeln!(w, "solid")?;
+        for t in tris {
+          let n = facet_normal(&t);
+          writeln!(w, "facet normal {} {} {}\nouter loop", n[0], n[1], n[2])?;
+          for c in &t { writeln!(w, "vertex {} {} {}", c[0], c[1], c[2])? }
+          writeln!(w, "endloop\nendfacet")?;
+        }
+        writeln!(w, "endsolid")?;
+      },
+      Format::Binary => {
+        w.write_all(&[0; HEADER_LEN])?;
+        w.write_all(&(tris.clone().count() as u32).to_le_bytes())?;
+        for t in tris {
+          let n = facet_normal(&t);
+          for p in std::iter::once(&n).chain(&t) {
+            for v in p.iter() { w.write_all(&v.to_le_bytes())? }
+          }
+          w.write_all(&[0; 2])?;
+        }
+      },
+    };
+    Ok(())
+  }
+}
+
+// Gives indices to corners, sharing them between corners at the same position if weld is set
+struct Welder {
+  weld: bool,
+  indices: HashMap<[u32; 3], usize>,
+}
+
+impl Welder {
+  fn index(&mut self, pts: &mut Vec<Point<3>>, p: Point<3>) -> usize {
+    let next = pts.len();
+    let i = if self.weld {
+      // adding zero turns -0 into 0 so that both weld together
+      let key = [p[0] + 0., p[1] + 0., p[2] + 0.].map(f32::to_bits);
+      *self.indices.entry(key).or_insert(next)
+    } else { next };
+    if i == next { pts.push(p) };
+    i
+  }
+}
+
+fn parse_point(args: &[&str]) -> ParseResult<Point<3>> {
+  if args.len() != 3 { return Err(ErrorKind::ValueCount{ min: 3, max: 3, found: args.len() }) };
+  args.iter()
+    .map(|a| a.parse::<f32>().map_err(|_| ErrorKind::InvalidNumber(a.to_string())))
+    .collect()
+}
+
+// binary files may also begin with "solid", so the size is checked first. Binary files with
+// trailing bytes fail that check, so text is also expected to have a facet or the end of the
+// solid near its start, and no bytes outside ascii.
+fn is_binary(buf: &[u8]) -> bool {
+  let start = buf.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(buf.len());
+  let solid = buf[start..].starts_with(b"solid");
+  if buf.len() < HEADER_LEN + 4 { return !solid };
+  let count = u32::from_le_bytes(buf[HEADER_LEN..HEADER_LEN + 4].try_into().unwrap()) as usize;
+  if buf.len() == HEADER_LEN + 4 + count * TRIANGLE_LEN || !solid { return true };
+  let head = &buf[start..buf.len().min(start + 512)];
+  let has = |word: &[u8]| head.windows(word.len()).any(|w| w == word);
+  !head.is_ascii() || !(has(b"facet") || has(b"endsolid"))
+}
+
+fn facet_normal(t: &[Point<3>; 3]) -> Point<3> {
+  let a: Vec<_> = (0..3).map(|i| t[1][i] - t[0][i]).collect();
+  let b: Vec<_> = (0..3).map(|i| t[2][i] - t[0][i]).collect();
+  let n = Point::from((a[1]*b[2] - a[2]*b[1], a[2]*b[0] - a[0]*b[2], a[0]*b[1] - a[1]*b[0]));
+  let len = n.dist(&Point::default());
+  if len == 0. { n } else { n.iter().map(|v| v/len).collect() }
+}
+
+#[cfg(test)]
+mod stl_test {
+  use super::Format;
+  use crate::{
+    mesh::Mesh,
+    point::Point,
+    error::{Error, ErrorKind},
+  };
+  const SQUARE: &str = "solid square
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 1
+    outer loop
+      vertex -0 0 0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid square
+";
+  #[test]
+  fn read_stl() {
+    let soup = Mesh::read_stl(SQUARE.as_bytes(), false).unwrap();
+    assert_eq!(soup.pts.len(), 6);
+    assert_eq!(soup.normals, vec!(Point::from((0., 0., 1.)); 2));
+    let m = Mesh::read_stl(SQUARE.as_bytes(), true).unwrap();
+    assert_eq!(m.pts.len(), 4);
+    assert_eq!(m.polygons, vec!(
+      vec!((0, None, Some(0)), (1, None, Some(0)), (2, None, Some(0))),
+      vec!((0, None, Some(1)), (2, None, Some(1)), (3, None, Some(1))),
+    ));
+  }
+  #[test]
+  fn round_trip() {
+    let m = Mesh::read_stl(SQUARE.as_bytes(), true).unwrap();
+    for &format in &[Format::Ascii, Format::Binary] {
+      let mut buf = vec!();
+      m.write_stl(&mut buf, format).unwrap();
+      assert_eq!(Mesh::read_stl(buf.as_slice(), true).unwrap(), m);
+    }
+    // a quad is split into two triangles
+    let mut quad = m.clone();
+    quad.polygons = vec!((0..4).map(|i| (i, None, None)).collect());
+    let mut buf = vec!();
+    quad.write_stl(&mut buf, Format::Binary).unwrap();
+    assert_eq!(buf.len(), 84 + 2 * 50);
+    assert_eq!(Mesh::read_stl(buf.as_slice(), true).unwrap(), m);
+    // a binary header starting with "solid" and bytes past the last triangle
+    buf[..11].copy_from_slice(b"solid quad ");
+    buf.extend_from_slice(b"\n\n");
+    assert_eq!(Mesh::read_stl(buf.as_slice(), true).unwrap(), m);
+  }
+  #[test]
+  fn stl_errors() {
+    let line = |s: &str| Mesh::read_stl(s.as_bytes(), false).unwrap_err().line();
+    assert_eq!(line("solid\nfacet normal 0 0 1\nouter loop\nvertex 0 0\n"), Some(4));
+    assert_eq!(line("solid\nfacet normal 0 0 1\nvertex 0 0 0\nendfacet\n"), Some(4));
+    assert_eq!(line("solid\nvertex 0 0 0\n"), Some(2));
+    let mut truncated = vec!(0u8; 84);
+    truncated[80] = 1;
+    assert!(matches!(Mesh::read_stl(truncated.as_slice(), false),
+      Err(Error::Invalid(ErrorKind::UnexpectedEof))));
+  }
+}
@@ -1,3 +1,13 @@
+use crate::error::{Error, ErrorKind, Result};
+
+// the line and kind of a parse error, panicking on any other result
+pub fn expect_parse_error<T: std::fmt::Debug>(r: Result<T>) -> (usize, ErrorKind) {
+  match r {
+    Err(Error::Parse{ line, kind }) => (line, kind),
+    r => panic!("expected parse error, got {:?}", r),
+  }
+}
+
 // Just a bad implementation of random for testing
 // Didn't want to bring in the crate just for that
 pub struct BadRand(i64);
@@ -0,0 +1,310 @@
+use crate::{
+  point::Point,
+  scalar::Scalar,
+  util::exact_f32,
+  error::{Error, ErrorKind, Result, ParseResult},
+};
+use std::{
+  io::{BufReader, BufRead, BufWriter, Write},
+  path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+  // any run of spaces or tabs
+  Whitespace,
+  Char(char),
+}
+
+// Column of a row, either by position from 0 or by its name in the header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+  Index(usize),
+  Name(String),
+}
+
+// Layout of a delimited text file of points, one per row.
+// Lines are read in order as skipped lines, a count of points, a header naming the columns and
+// then rows, where the count and header are only present if enabled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextFormat {
+  pub delimiter: Delimiter,
+  pub skip: usize,
+  // whether there is a line with the number of points, as in PTS files
+  pub count: bool,
+  pub header: bool,
+  // lines starting with any of these are ignored
+  pub comments: Vec<String>,
+  pub xyz: [Column; 3],
+  // names and columns of other values kept for each point
+  pub attributes: Vec<(String, Column)>,
+}
+
+impl Default for TextFormat {
+  fn default() -> Self { Self::xyz() }
+}
+
+impl TextFormat {
+  // whitespace separated x, y and z in the first three columns
+  pub fn xyz() -> Self {
+    TextFormat{
+      delimiter: Delimiter::Whitespace,
+      skip: 0,
+      count: false,
+      header: false,
+      comments: vec!("#".to_string(), "//".to_string()),
+      xyz: [Column::Index(0), Column::Index(1), Column::Index(2)],
+      attributes: vec!(),
+    }
+  }
+  // comma separated with a header which names the x, y and z columns
+  pub fn csv() -> Self {
+    let name = |n: &str| Column::Name(n.to_string());
+    TextFormat{
+      delimiter: Delimiter::Char(','),
+      header: true,
+      xyz: [name("x"), name("y"), name("z")],
+      ..Self::xyz()
+    }
+  }
+  // whitespace separated x, y and z after a line with the number of points
+  pub fn pts() -> Self { TextFormat{ count: true, ..Self::xyz() } }
+  pub fn with_attribute(mut self, name: &str, c: Column) -> Self {
+    self.attributes.push((name.to_string(), c));
+    self
+  }
+  fn split<'a>(&self, line: &'a str) -> Vec<&'a str> {
+    match self.delimiter {
+      Delimiter::Whitespace => line.split_whitespace().collect(),
+      Delimiter::Char(c) => line.split(c).map(|v| v.trim()).collect(),
+    }
+  }
+  fn is_comment(&self, line: &str) -> bool {
+    let line = line.trim_start();
+    line.is_empty() || self.comments.iter().any(|c| line.starts_with(c.as_str()))
+  }
+  // finds the position of each column, using the header for named columns
+  fn resolve(&self, header: &[&str]) -> ParseResult<Vec<usize>> {
+    self.xyz.iter().chain(self.attributes.iter().map(|(_, c)| c)).map(|c| match c {
+      Column::Index(i) => Ok(*i),
+      Column::Name(n) => header.iter()
+        .position(|h| h.trim_matches('"').eq_ignore_ascii_case(n))
+        .ok_or_else(|| ErrorKind::MissingProperty(n.clone())),
+    }).collect()
+  }
+  // finds where each column is written, returning the position of each and the header line.
+  // Named columns take the first positions not given by an index, and positions which no column
+  // takes are named _. Fails for layouts which would not be read back the same.
+  fn layout(&self) -> ParseResult<(Vec<usize>, Vec<&str>)> {
+    let columns: Vec<_> = ["x", "y", "z"].iter().copied().zip(&self.xyz)
+      .chain(self.attributes.iter().map(|(n, c)| (n.as_str(), c)))
+      .collect();
+    let taken: Vec<_> = columns.iter().filter_map(|(_, c)| match c {
+      Column::Index(i) => Some(*i),
+      Column::Name(_) => None,
+    }).collect();
+    let mut free = (0..).filter(|i| !taken.contains(i));
+    let positions: Vec<_> = columns.iter().map(|(_, c)| match c {
+      Column::Index(i) => *i,
+      Column::Name(_) => free.next().unwrap(),
+    }).collect();
+    let mut header = vec!("_"; positions.iter().max().unwrap() + 1);
+    for (&i, (default, c)) in positions.iter().zip(&columns) {
+      if positions.iter().filter(|&&j| j == i).count() > 1 {
+        return Err(ErrorKind::Malformed(format!("column {} is used more than once", i)))
+      }
+      header[i] = match c {
+        Column::Name(n) => n,
+        Column::Index(_) => default,
+      };
+    }
+    for (_, c) in &columns {
+      let n = match c {
+        Column::Name(n) => n,
+        Column::Index(_) => continue,
+      };
+      if !self.header { return Err(ErrorKind::MissingProperty(n.clone())) };
+      if header.iter().filter(|h| h.eq_ignore_ascii_case(n)).count() > 1 {
+        return Err(ErrorKind::Malformed(format!("column {:?} is named more than once", n)))
+      }
+    }
+    Ok((positions, header))
+  }
+  pub fn read_file<S: Scalar, P: AsRef<Path>>(
+    &self,
+    path: P,
+  ) -> Result<Vec<(Point<3, S>, Vec<f64>)>> {
+    self.read(BufReader::new(std::fs::File::open(path)?))
+  }
+  // reads each row as a point and the values of the attributes in order
+  pub fn read<S: Scalar, R: BufRead>(&self, r: R) -> Result<Vec<(Point<3, S>, Vec<f64>)>> {
+    let mut lines = r.lines().enumerate().skip(self.skip)
+      .filter(|(_, l)| l.as_ref().map_or(true, |l| !self.is_comment(l)));
+    let mut next = |what: &str| -> Result<Option<(usize, String)>> {
+      match lines.next() {
+        None if what.is_empty() => Ok(None),
+        None => Err(Error::Invalid(ErrorKind::MissingProperty(what.to_string()))),
+        Some((i, l)) => Ok(Some((i + 1, l?))),
+      }
+    };
+    let count = if self.count {
+      let (line_num, l) = next("count")?.unwrap();
+      let n = l.trim().parse::<usize>()
+        .map_err(|_| Error::parse(line_num, ErrorKind::InvalidNumber(l.trim().to_string())))?;
+      Some((line_num, n))
+    } else {
+      None
+    };
+    let columns = if self.header {
+      let (line_num, l) = next("header")?.unwrap();
+      self.resolve(&self.split(&l)).map_err(|kind| Error::parse(line_num, kind))?
+    } else {
+      self.resolve(&[]).map_err(Error::Invalid)?
+    };
+    let needed = columns.iter().max().unwrap() + 1;
+    let mut out = vec!();
+    while let Some((line_num, l)) = next("")? {
+      let row = self.split(&l);
+      let parse = |i: usize| -> ParseResult<f64> {
+        if row.len() < needed {
+          return Err(ErrorKind::ValueCount{ min: needed, max: usize::MAX, found: row.len() })
+        }
+        row[i].parse::<f64>().map_err(|_| ErrorKind::InvalidNumber(row[i].to_string()))
+      };
+      let vals = columns.iter().map(|&i| parse(i)).collect::<ParseResult<Vec<_>>>()
+        .map_err(|kind| Error::parse(line_num, kind))?;
+      let p = Point::from([vals[0], vals[1], vals[2]].map(S::from_f64));
+      out.push((p, vals[3..].to_vec()));
+    }
+    match count {
+      // the row count is reported against the line that declared it
+      Some((line_num, n)) if n != out.len() => Err(Error::parse(line_num,
+        ErrorKind::ValueCount{ min: n, max: n, found: out.len() })),
+      _ => Ok(out),
+    }
+  }
+  pub fn write_file<S: Scalar, P: AsRef<Path>>(
+    &self,
+    path: P,
+    pts: &[(Point<3, S>, Vec<f64>)],
+  ) -> Result<()> {
+    let mut w = BufWriter::new(std::fs::File::create(path)?);
+    self.write(&mut w, pts)?;
+    Ok(w.flush()?)
+  }
+  // writes x, y, z and the attributes of each point into the columns they are read from, along
+  // with the count and header if they are enabled. Columns which hold none of them are written
+  // as 0.
+  pub fn write<S: Scalar, W: Write>(
+    &self,
+    mut w: W,
+    pts: &[(Point<3, S>, Vec<f64>)],
+  ) -> Result<()> {
+    let delim = match self.delimiter {
+      Delimiter::Whitespace => " ".to_string(),
+      Delimiter::Char(c) => c.to_string(),
+    };
+    let (positions, header) = self.layout().map_err(Error::Invalid)?;
+    if self.count { writeln!(w, "{}", pts.len())? };
+    if self.header { writeln!(w, "{}", header.join(&delim))? };
+    for (p, attrs) in pts {
+      if attrs.len() != self.attributes.len() {
+        let n = self.attributes.len();
+        return Err(Error::Invalid(ErrorKind::ValueCount{ min: n, max: n, found: attrs.len() }))
+      }
+      let mut row = vec!(0.; header.len());
+      let vals = p.iter().map(|v| v.to_f64()).chain(attrs.iter().copied());
+      positions.iter().zip(vals).for_each(|(&i, v)| row[i] = v);
+      let row: Vec<_> = row.into_iter()
+        .map(|v| if exact_f32(v) { (v as f32).to_string() } else { v.to_string() })
+        .collect();
+      writeln!(w, "{}", row.join(&delim))?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod text_test {
+  use super::{TextFormat, Column, Delimiter};
+  use crate::{
+    point::Point,
+    error::{Error, ErrorKind},
+    test_util::expect_parse_error,
+  };
+  #[test]
+  fn read_text() {
+    let xyz = "# scan\n1 2 3\n\n  4\t5 6 0.5\n// done\n";
+    let pts = TextFormat::xyz().read::<f32, _>(xyz.as_bytes()).unwrap();
+    assert_eq!(pts, vec!((Point::from((1., 2., 3.)), vec!()), (Point::from((4., 5., 6.)), vec!())));
+    let csv = "exported by scanner\n\"Intensity\",\"Z\",\"Y\",\"X\"\n10, 3, 2, 1\n";
+    let f = TextFormat{ skip: 1, ..TextFormat::csv() }
+      .with_attribute("intensity", Column::Name("intensity".to_string()));
+    let pts = f.read::<f64, _>(csv.as_bytes()).unwrap();
+    assert_eq!(pts, vec!((Point::from((1., 2., 3.)), vec!(10.))));
+    let pts = TextFormat::pts()
+      .with_attribute("intensity", Column::Index(3))
+      .read::<f32, _>("2\n0 0 0 -5\n1 1 1 7\n".as_bytes())
+      .unwrap();
+    assert_eq!(pts[1], (Point::from((1., 1., 1.)), vec!(7.)));
+  }
+  #[test]
+  fn round_trip() {
+    let pts = vec!(
+      (Point::<3, f64>::from((0.1, 2., 1e-300)), vec!(4.)),
+      (Point::from(1.), vec!(-2.)),
+    );
+    let formats = [
+      TextFormat::xyz(),
+      TextFormat{ delimiter: Delimiter::Char(';'), ..TextFormat::csv() },
+      TextFormat::pts(),
+    ];
+    for f in formats.iter() {
+      let f = f.clone().with_attribute("i", Column::Index(3));
+      let mut buf = vec!();
+      f.write(&mut buf, &pts).unwrap();
+      assert_eq!(f.read::<f64, _>(buf.as_slice()).unwrap(), pts);
+    }
+    let mut buf = vec!();
+    TextFormat::csv().write(&mut buf, &[(Point::<3>::from((0.1, 0., 0.)), vec!())]).unwrap();
+    assert_eq!(String::from_utf8(buf).unwrap(), "x,y,z\n0.1,0,0\n");
+  }
+  #[test]
+  fn permuted_columns() {
+    let pts = vec!((Point::<3, f64>::from((1., 2., 3.)), vec!(4., 5.)));
+    let zyx = TextFormat{
+      xyz: [Column::Index(2), Column::Index(1), Column::Index(0)],
+      ..TextFormat::xyz()
+    }.with_attribute("i", Column::Index(5)).with_attribute("t", Column::Index(3));
+    let mut buf = vec!();
+    zyx.write(&mut buf, &pts).unwrap();
+    assert_eq!(String::from_utf8(buf.clone()).unwrap(), "3 2 1 5 0 4\n");
+    assert_eq!(zyx.read::<f64, _>(buf.as_slice()).unwrap(), pts);
+    let csv = TextFormat::csv()
+      .with_attribute("i", Column::Name("intensity".to_string()))
+      .with_attribute("t", Column::Index(1));
+    let mut buf = vec!();
+    csv.write(&mut buf, &pts).unwrap();
+    assert_eq!(String::from_utf8(buf.clone()).unwrap(), "x,t,y,z,intensity\n1,5,2,3,4\n");
+    assert_eq!(csv.read::<f64, _>(buf.as_slice()).unwrap(), pts);
+    let invalid = |f: TextFormat| matches!(f.write(vec!(), &pts), Err(Error::Invalid(_)));
+    assert!(invalid(zyx.clone().with_attribute("twice", Column::Index(0))));
+    assert!(invalid(TextFormat::xyz().with_attribute("i", Column::Name("i".to_string()))));
+    assert!(invalid(TextFormat::csv().with_attribute("x", Column::Index(3))));
+  }
+  #[test]
+  fn text_errors() {
+    let err = |f: &TextFormat, s: &str| expect_parse_error(f.read::<f32, _>(s.as_bytes()));
+    let xyz = TextFormat::xyz();
+    assert_eq!(err(&xyz, "# c\n1 2 3\n1 2\n"),
+      (3, ErrorKind::ValueCount{ min: 3, max: usize::MAX, found: 2 }));
+    assert_eq!(err(&xyz, "1 2 3\n\n1 2 z\n"), (3, ErrorKind::InvalidNumber("z".to_string())));
+    assert_eq!(err(&TextFormat::csv(), "x,y,w\n"),
+      (1, ErrorKind::MissingProperty("z".to_string())));
+    assert_eq!(err(&TextFormat::pts(), "many\n").0, 1);
+    assert_eq!(err(&TextFormat::pts(), "# c\n3\n0 0 0 1\n1 1 1 2\n"),
+      (2, ErrorKind::ValueCount{ min: 3, max: 3, found: 2 }));
+    assert!(matches!(TextFormat::csv().read::<f32, _>("".as_bytes()),
+      Err(Error::Invalid(ErrorKind::MissingProperty(_)))));
+  }
+}
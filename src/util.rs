@@ -1,17 +1,22 @@
-// Finds average of an iterator f32s
+use crate::scalar::Scalar;
+
+// Finds average of an iterator of scalars, only meaningful for floats
 // Caution when using on large streams
-pub fn average<I>(v: I) -> f32 where I: IntoIterator<Item = f32> {
-  v.into_iter().enumerate().fold(0., |prev, (n, c)| {
-    let n = n as f32;
-    (n * prev + c)/(n + 1.)
+pub fn average<S: Scalar, I>(v: I) -> S where I: IntoIterator<Item = S> {
+  v.into_iter().enumerate().fold(S::ZERO, |prev, (n, c)| {
+    let n = S::from_f64(n as f64);
+    (n * prev + c)/(n + S::ONE)
   })
 }
 
-// Finds variance of an iterator f32s
-pub fn variance<I>(v: I) -> f32 where I: IntoIterator<Item = f32> + Clone {
+// whether a value survives being stored as an f32
+pub fn exact_f32(v: f64) -> bool { v as f32 as f64 == v }
+
+// Finds variance of an iterator of scalars, only meaningful for floats
+pub fn variance<S: Scalar, I>(v: I) -> S where I: IntoIterator<Item = S> + Clone {
   let iter = v.clone().into_iter();
   let avg = average(v);
-  average(iter.map(|f| (f - avg).powi(2)))
+  average(iter.map(|f| (f - avg) * (f - avg)))
 }
 
 #[cfg(test)]
@@ -33,6 +38,151 @@ mod average_variance_tests {
   }
 }
 
+// eigenvalues of a symmetric matrix in increasing order, each with its unit eigenvector, found
+// by cyclic Jacobi rotations
+pub fn symmetric_eigen<const N: usize>(mut a: [[f64; N]; N]) -> [(f64, [f64; N]); N] {
+  let mut v = [[0.; N]; N];
+  (0..N).for_each(|i| v[i][i] = 1.);
+  let scale: f64 = a.iter().flat_map(|r| r.iter()).map(|x| x * x).sum();
+  for _ in 0..64 {
+    let off: f64 = (0..N).flat_map(|i| (0..N).filter(move |&j| j != i).map(move |j| (i, j)))
+      .map(|(i, j)| a[i][j] * a[i][j])
+      .sum();
+    if off <= scale * 1e-30 { break };
+    for p in 0..N {
+      for q in p+1..N {
+        if a[p][q] == 0. { continue };
+        // rotation by the angle which zeroes a[p][q]
+        let theta = (a[q][q] - a[p][p]) / (2. * a[p][q]);
+        let t = theta.signum() / (theta.abs() + (theta * theta + 1.).sqrt());
+        let c = 1. / (t * t + 1.).sqrt();
+        let s = t * c;
+        let rotate = |x: f64, y: f64| (c * x - s * y, s * x + c * y);
+        // columns p and q of a and v, then rows p and q of a
+        for row in a.iter_mut().chain(v.iter_mut()) {
+          (row[p], row[q]) = rotate(row[p], row[q]);
+        }
+        let (lo, hi) = a.split_at_mut(q);
+        for (x, y) in lo[p].iter_mut().zip(hi[0].iter_mut()) {
+          (*x, *y) = rotate(*x, *y);
+        }
+      }
+    }
+  }
+  let mut out = [(0., [0.; N]); N];
+  for (i, o) in out.iter_mut().enumerate() {
+    *o = (a[i][i], v.map(|row| row[i]));
+  }
+  out.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap_or(std::cmp::Ordering::Equal));
+  out
+}
+
+#[cfg(test)]
+mod eigen_tests {
+  use super::symmetric_eigen;
+  #[test]
+  fn symmetric() {
+    let m = [[4., 1., 2.], [1., 3., 0.], [2., 0., 5.]];
+    let eig = symmetric_eigen(m);
+    assert!(eig[0].0 <= eig[1].0 && eig[1].0 <= eig[2].0);
+    assert!((eig.iter().map(|e| e.0).sum::<f64>() - 12.).abs() < 1e-9);
+    for (val, vec) in eig.iter() {
+      for i in 0..3 {
+        let mv: f64 = (0..3).map(|j| m[i][j] * vec[j]).sum();
+        assert!((mv - val * vec[i]).abs() < 1e-9);
+      }
+      assert!((vec.iter().map(|x| x * x).sum::<f64>() - 1.).abs() < 1e-9);
+    }
+    let diag = symmetric_eigen([[2., 0.], [0., -1.]]);
+    assert_eq!(diag, [(-1., [0., 1.]), (2., [1., 0.])]);
+  }
+}
+
+// solves a x = b by Gaussian elimination with partial pivoting, or None if a is singular
+pub fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+  let n = b.len();
+  let scale = a.iter().flat_map(|r| r.iter()).fold(0f64, |m, x| m.max(x.abs()));
+  for c in 0..n {
+    let p = (c..n).fold(c, |p, i| if a[i][c].abs() > a[p][c].abs() { i } else { p });
+    if a[p][c].abs() <= scale * 1e-12 { return None };
+    a.swap(c, p);
+    b.swap(c, p);
+    for r in c+1..n {
+      let f = a[r][c] / a[c][c];
+      let (top, rest) = a.split_at_mut(r);
+      rest[0][c..].iter_mut().zip(&top[c][c..]).for_each(|(x, y)| *x -= f * y);
+      b[r] -= f * b[c];
+    }
+  }
+  let mut x = vec!(0.; n);
+  for c in (0..n).rev() {
+    let rest: f64 = (c+1..n).map(|k| a[c][k] * x[k]).sum();
+    x[c] = (b[c] - rest) / a[c][c];
+  }
+  Some(x)
+}
+
+#[cfg(test)]
+mod solve_tests {
+  use super::solve;
+  #[test]
+  fn linear_system() {
+    let x = solve(vec!(vec!(0., 2., 1.), vec!(1., 1., 0.), vec!(3., 0., 1.)), vec!(5., 3., 4.));
+    let x = x.unwrap();
+    for (v, e) in x.iter().zip(&[1., 2., 1.]) { assert!((v - e).abs() < 1e-12) }
+    assert_eq!(solve(vec!(vec!(1., 2.), vec!(2., 4.)), vec!(1., 2.)), None);
+    assert_eq!(solve(vec!(vec!(0.)), vec!(0.)), None);
+  }
+}
+
+// Small deterministic generator (splitmix64) for randomized algorithms, which should give the
+// same result for the same seed
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+  pub fn new(seed: u64) -> Self { Rng(seed) }
+  pub fn next_u64(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+  }
+  // uniform in 0..n, for n > 0
+  pub fn below(&mut self, n: usize) -> usize { (self.next_u64() % n as u64) as usize }
+  // k different values in 0..n, which is meant for k much smaller than n
+  pub fn distinct(&mut self, n: usize, k: usize) -> Vec<usize> {
+    assert!(k <= n);
+    let mut out = Vec::with_capacity(k);
+    while out.len() < k {
+      let i = self.below(n);
+      if !out.contains(&i) { out.push(i) };
+    }
+    out
+  }
+}
+
+#[cfg(test)]
+mod rng_tests {
+  use super::Rng;
+  #[test]
+  fn deterministic() {
+    let draw = |seed| {
+      let mut r = Rng::new(seed);
+      (0..8).map(|_| r.below(10)).collect::<Vec<_>>()
+    };
+    assert_eq!(draw(3), draw(3));
+    assert_ne!(draw(3), draw(4));
+    let mut r = Rng::new(0);
+    for _ in 0..100 {
+      let mut d = r.distinct(5, 5);
+      d.sort();
+      assert_eq!(d, vec!(0, 1, 2, 3, 4));
+    }
+  }
+}
+
 
 // The below could be abstracted over orderable types, but not really worth it yet
 